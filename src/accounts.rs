@@ -0,0 +1,498 @@
+use {
+    crate::{
+        metrics,
+        report::RenderReport,
+        source::{BlockStreamSource, Interruption, Reconnects, ReferenceAccountSource},
+    },
+    log::{error, info, warn},
+    serde::Serialize,
+    solana_client::rpc_response::UiAccount,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        io::{self, Write},
+        sync::Mutex,
+        time::Duration,
+    },
+    yellowstone_grpc_proto::geyser::{
+        SlotStatus, SubscribeRequest, SubscribeUpdateAccount, subscribe_update::UpdateOneof,
+    },
+};
+
+// getMultipleAccounts accepts at most 100 pubkeys per request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+#[derive(Debug, Default, Serialize)]
+pub struct AccountReport {
+    pub total_updates: u64,
+    pub verified_accounts: u64,
+    pub mismatched_accounts: u64,
+    pub inconclusive_accounts: u64,
+    pub stalls: u64,
+    // mismatched and inconclusive checks; matches are only counted
+    pub accounts: Vec<AccountRecord>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStatus {
+    Mismatch,
+    Inconclusive,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountRecord {
+    pub pubkey: String,
+    pub slot: u64,
+    pub status: AccountStatus,
+    pub fields: Vec<&'static str>,
+    // why an inconclusive account could not be compared
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AccountCsvRow<'a> {
+    pubkey: &'a str,
+    slot: u64,
+    status: AccountStatus,
+    fields: String,
+    reason: Option<&'a str>,
+}
+
+impl RenderReport for AccountReport {
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\n========== FINAL ACCOUNT REPORT ==========")?;
+        writeln!(
+            out,
+            "Total Account Updates Received: {}",
+            self.total_updates
+        )?;
+        writeln!(out, "Verified Accounts: {}", self.verified_accounts)?;
+        writeln!(out, "Mismatched Accounts: {}", self.mismatched_accounts)?;
+        writeln!(out, "Inconclusive Accounts: {}", self.inconclusive_accounts)?;
+        if self.stalls > 0 {
+            writeln!(out, "Stalls Detected: {}", self.stalls)?;
+        }
+
+        if self.mismatched_accounts > 0 {
+            writeln!(out, "\n--- MISMATCH DETAILS ---")?;
+            for record in self
+                .accounts
+                .iter()
+                .filter(|r| r.status == AccountStatus::Mismatch)
+            {
+                writeln!(
+                    out,
+                    "Account {} mismatch at slot {}: {}",
+                    record.pubkey,
+                    record.slot,
+                    record.fields.join(", ")
+                )?;
+            }
+        }
+        if self.inconclusive_accounts > 0 {
+            writeln!(out, "\n--- INCONCLUSIVE DETAILS ---")?;
+            for record in self
+                .accounts
+                .iter()
+                .filter(|r| r.status == AccountStatus::Inconclusive)
+            {
+                writeln!(
+                    out,
+                    "Account {} inconclusive at slot {}: {}",
+                    record.pubkey,
+                    record.slot,
+                    record.reason.as_deref().unwrap_or_default()
+                )?;
+            }
+        }
+        writeln!(out, "===========================================")
+    }
+
+    fn write_csv(&self, out: &mut dyn Write) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_writer(out);
+        for record in &self.accounts {
+            writer.serialize(AccountCsvRow {
+                pubkey: &record.pubkey,
+                slot: record.slot,
+                status: record.status,
+                fields: record.fields.join(";"),
+                reason: record.reason.as_deref(),
+            })?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AccountSnapshot {
+    slot: u64,
+    write_version: u64,
+    lamports: u64,
+    owner: Pubkey,
+    executable: bool,
+    rent_epoch: u64,
+    data: Vec<u8>,
+}
+
+impl AccountSnapshot {
+    fn diff(&self, other: &Self) -> Vec<&'static str> {
+        let mut fields = vec![];
+        if self.lamports != other.lamports {
+            fields.push("lamports");
+        }
+        if self.owner != other.owner {
+            fields.push("owner");
+        }
+        if self.executable != other.executable {
+            fields.push("executable");
+        }
+        if self.rent_epoch != other.rent_epoch {
+            fields.push("rent_epoch");
+        }
+        if self.data != other.data {
+            fields.push("data");
+        }
+        fields
+    }
+}
+
+// RPC answered from a later slot than the one being verified and the states
+// differ; re-checked once the stream has caught up with `context_slot`.
+#[derive(Debug)]
+struct Deferred {
+    pubkey: Pubkey,
+    context_slot: u64,
+    rpc: Option<AccountSnapshot>,
+}
+
+#[derive(Debug, Default)]
+struct AccountState {
+    // the newest streamed state of every account not verified yet
+    latest: HashMap<Pubkey, AccountSnapshot>,
+    pending: BTreeMap<u64, HashSet<Pubkey>>,
+    deferred: Vec<Deferred>,
+}
+
+impl AccountState {
+    fn apply_update(&mut self, update: SubscribeUpdateAccount) -> anyhow::Result<()> {
+        let Some(account) = update.account else {
+            return Ok(());
+        };
+        let pubkey = Pubkey::try_from(account.pubkey.as_slice())
+            .map_err(|_| anyhow::anyhow!("invalid account pubkey"))?;
+        let snapshot = AccountSnapshot {
+            slot: update.slot,
+            write_version: account.write_version,
+            lamports: account.lamports,
+            owner: Pubkey::try_from(account.owner.as_slice())
+                .map_err(|_| anyhow::anyhow!("invalid account owner"))?,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data,
+        };
+
+        let newer = self.latest.get(&pubkey).is_none_or(|current| {
+            (snapshot.slot, snapshot.write_version) > (current.slot, current.write_version)
+        });
+        if newer {
+            self.latest.insert(pubkey, snapshot);
+        }
+        self.pending.entry(update.slot).or_default().insert(pubkey);
+        Ok(())
+    }
+
+    // Forgets `pubkey` once its state as of `slot` is verified, unless a later
+    // update is still waiting for its slot to be finalized.
+    fn verified(&mut self, pubkey: &Pubkey, slot: u64) {
+        if self
+            .latest
+            .get(pubkey)
+            .is_some_and(|latest| latest.slot <= slot)
+        {
+            self.latest.remove(pubkey);
+        }
+    }
+}
+
+// Verifies a stream of account updates against `reference`, one finalized
+// slot at a time.
+pub struct AccountVerifier<R> {
+    pub report: Mutex<AccountReport>,
+    reference: R,
+    state: Mutex<AccountState>,
+    stall_timeout: Option<Duration>,
+    // the `provider` label of the metrics
+    provider: String,
+}
+
+impl<R: ReferenceAccountSource> AccountVerifier<R> {
+    pub fn new(reference: R, stall_timeout: Option<Duration>) -> Self {
+        Self {
+            report: Mutex::new(AccountReport::default()),
+            reference,
+            state: Mutex::new(AccountState::default()),
+            stall_timeout,
+            provider: String::new(),
+        }
+    }
+
+    // Labels the metrics of this verifier with `provider`.
+    pub fn labelled(mut self, provider: &str) -> Self {
+        metrics::init_provider(provider);
+        self.provider = provider.to_string();
+        self
+    }
+
+    // Subscribes to `source` with `request`, which should select the accounts
+    // together with finalized slot updates, reconnecting until it fails
+    // permanently.
+    pub async fn stream(
+        &self,
+        source: &impl BlockStreamSource,
+        request: SubscribeRequest,
+    ) -> anyhow::Result<()> {
+        let reconnects = Reconnects::new("Account stream");
+        let (report, state) = (&self.report, &self.state);
+
+        reconnects
+            .run(|| {
+                let (request, reconnects) = (&request, &reconnects);
+                async move {
+                    let mut subscription = reconnects
+                        .subscribe(source, request.clone(), self.stall_timeout)
+                        .await
+                        .map_err(backoff::Error::transient)?;
+
+                    loop {
+                        let update = match subscription.next().await {
+                            Ok(update) => update,
+                            Err(Interruption::Stalled(quiet)) => {
+                                warn!("STALL → no account update for {:?}, reconnecting", quiet);
+                                report.lock().unwrap().stalls += 1;
+                                metrics::STALLS.with_label_values(&[&self.provider]).inc();
+                                break;
+                            }
+                            Err(_) => break,
+                        };
+                        metrics::record_stream_message(
+                            &self.provider,
+                            update.update_oneof.as_ref(),
+                        );
+
+                        match update.update_oneof {
+                            Some(UpdateOneof::Account(account)) => {
+                                subscription.received_data();
+                                report.lock().unwrap().total_updates += 1;
+                                if let Err(e) = state.lock().unwrap().apply_update(account) {
+                                    error!("Account update error: {:?}", e);
+                                }
+                            }
+                            Some(UpdateOneof::Slot(slot))
+                                if slot.status == SlotStatus::SlotFinalized as i32 =>
+                            {
+                                verify_finalized_slot(&self.reference, slot.slot, state, report)
+                                    .await;
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    Err(backoff::Error::transient(anyhow::anyhow!("Stream ended")))
+                }
+            })
+            .await
+    }
+
+    pub fn finish(self) -> AccountReport {
+        self.report.into_inner().unwrap()
+    }
+}
+
+async fn verify_finalized_slot(
+    reference: &impl ReferenceAccountSource,
+    slot: u64,
+    state: &Mutex<AccountState>,
+    report: &Mutex<AccountReport>,
+) {
+    let (pubkeys, deferred) = {
+        let mut state = state.lock().unwrap();
+        let later = state.pending.split_off(&(slot + 1));
+        let due = std::mem::replace(&mut state.pending, later);
+        let pubkeys: Vec<Pubkey> = due
+            .into_values()
+            .flatten()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let (deferred, waiting): (Vec<Deferred>, Vec<Deferred>) =
+            std::mem::take(&mut state.deferred)
+                .into_iter()
+                .partition(|d| d.context_slot <= slot);
+        state.deferred = waiting;
+        (pubkeys, deferred)
+    };
+
+    for deferred in deferred {
+        recheck_deferred(deferred, state, report);
+    }
+
+    for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        // without an answer every account of the chunk is inconclusive
+        let (context_slot, rpc_accounts): (_, Vec<Result<Option<UiAccount>, String>>) =
+            match reference.get_multiple_accounts(chunk, slot).await {
+                Ok(response) => (
+                    response.context.slot,
+                    response.value.into_iter().map(Ok).collect(),
+                ),
+                Err(e) => {
+                    error!("RPC getMultipleAccounts error at slot {}: {:?}", slot, e);
+                    let reason = format!("getMultipleAccounts failed: {}", e);
+                    (slot, vec![Err(reason); chunk.len()])
+                }
+            };
+
+        for (pubkey, rpc) in chunk.iter().zip(rpc_accounts) {
+            let Some(stream) = state.lock().unwrap().latest.get(pubkey).cloned() else {
+                continue;
+            };
+            if stream.slot > slot {
+                // superseded by a later update, verified when that slot finalizes
+                continue;
+            }
+            let rpc = rpc.and_then(|account| {
+                account
+                    .map(|account| rpc_snapshot(pubkey, account, context_slot))
+                    .transpose()
+                    .map_err(|e| e.to_string())
+            });
+            let rpc = match rpc {
+                Ok(rpc) => rpc,
+                Err(reason) => {
+                    record_inconclusive(report, pubkey, slot, reason);
+                    state.lock().unwrap().verified(pubkey, slot);
+                    continue;
+                }
+            };
+
+            let fields = diff_with_rpc(&stream, rpc.as_ref());
+            if fields.is_empty() {
+                record_match(report, pubkey, slot);
+            } else if context_slot > slot {
+                state.lock().unwrap().deferred.push(Deferred {
+                    pubkey: *pubkey,
+                    context_slot,
+                    rpc,
+                });
+                continue;
+            } else {
+                record_mismatch(report, pubkey, slot, &fields);
+            }
+            state.lock().unwrap().verified(pubkey, slot);
+        }
+    }
+}
+
+fn recheck_deferred(
+    deferred: Deferred,
+    state: &Mutex<AccountState>,
+    report: &Mutex<AccountReport>,
+) {
+    let Some(stream) = state.lock().unwrap().latest.get(&deferred.pubkey).cloned() else {
+        return;
+    };
+
+    if stream.slot > deferred.context_slot {
+        let reason = format!(
+            "RPC context slot {} superseded by stream slot {}",
+            deferred.context_slot, stream.slot
+        );
+        record_inconclusive(report, &deferred.pubkey, deferred.context_slot, reason);
+        return;
+    }
+
+    let fields = diff_with_rpc(&stream, deferred.rpc.as_ref());
+    if fields.is_empty() {
+        record_match(report, &deferred.pubkey, deferred.context_slot);
+    } else {
+        record_mismatch(report, &deferred.pubkey, deferred.context_slot, &fields);
+    }
+    state
+        .lock()
+        .unwrap()
+        .verified(&deferred.pubkey, deferred.context_slot);
+}
+
+fn rpc_snapshot(pubkey: &Pubkey, account: UiAccount, slot: u64) -> anyhow::Result<AccountSnapshot> {
+    let data = account
+        .data
+        .decode()
+        .ok_or_else(|| anyhow::anyhow!("RPC returned undecodable data for account {}", pubkey))?;
+    Ok(AccountSnapshot {
+        slot,
+        write_version: 0,
+        lamports: account.lamports,
+        owner: account
+            .owner
+            .parse()
+            .map_err(|_| anyhow::anyhow!("RPC returned an invalid owner for account {}", pubkey))?,
+        executable: account.executable,
+        rent_epoch: account.rent_epoch,
+        data,
+    })
+}
+
+// Accounts closed on chain are streamed with zero lamports while RPC returns null.
+fn diff_with_rpc(stream: &AccountSnapshot, rpc: Option<&AccountSnapshot>) -> Vec<&'static str> {
+    match rpc {
+        Some(rpc) => stream.diff(rpc),
+        None if stream.lamports == 0 => vec![],
+        None => vec!["missing"],
+    }
+}
+
+fn record_match(report: &Mutex<AccountReport>, pubkey: &Pubkey, slot: u64) {
+    report.lock().unwrap().verified_accounts += 1;
+    info!("MATCH account {} at slot {}", pubkey, slot);
+}
+
+fn record_mismatch(
+    report: &Mutex<AccountReport>,
+    pubkey: &Pubkey,
+    slot: u64,
+    fields: &[&'static str],
+) {
+    let mut rep = report.lock().unwrap();
+    rep.mismatched_accounts += 1;
+    rep.accounts.push(AccountRecord {
+        pubkey: pubkey.to_string(),
+        slot,
+        status: AccountStatus::Mismatch,
+        fields: fields.to_vec(),
+        reason: None,
+    });
+    info!(
+        "MISMATCH account {} at slot {} → {}",
+        pubkey,
+        slot,
+        fields.join(", ")
+    );
+}
+
+fn record_inconclusive(report: &Mutex<AccountReport>, pubkey: &Pubkey, slot: u64, reason: String) {
+    info!(
+        "INCONCLUSIVE account {} at slot {} → {}",
+        pubkey, slot, reason
+    );
+    let mut rep = report.lock().unwrap();
+    rep.inconclusive_accounts += 1;
+    rep.accounts.push(AccountRecord {
+        pubkey: pubkey.to_string(),
+        slot,
+        status: AccountStatus::Inconclusive,
+        fields: vec![],
+        reason: Some(reason),
+    });
+}
@@ -0,0 +1,35 @@
+use std::collections::BTreeMap;
+
+// slots whose blockhash is remembered behind the highest slot delivered, well
+// past the point where a processed slot is finalized or abandoned
+const RETAINED_SLOTS: u64 = 1_000;
+
+// Remembers the blockhash delivered for each recent slot, to tell a slot
+// delivered again with a different block (the stream switched forks) from a
+// plain duplicate.
+#[derive(Debug, Default)]
+pub struct ForkTracker {
+    blockhashes: BTreeMap<u64, String>,
+    highest: u64,
+}
+
+impl ForkTracker {
+    // Returns the blockhash previously delivered for `slot` when `blockhash` replaces it.
+    pub fn record(&mut self, slot: u64, blockhash: &str) -> Option<String> {
+        if slot + RETAINED_SLOTS < self.highest {
+            return None;
+        }
+        self.highest = self.highest.max(slot);
+        let previous = self.blockhashes.insert(slot, blockhash.to_string());
+
+        let retained_from = self.highest.saturating_sub(RETAINED_SLOTS);
+        if self
+            .blockhashes
+            .first_key_value()
+            .is_some_and(|(oldest, _)| *oldest < retained_from)
+        {
+            self.blockhashes = self.blockhashes.split_off(&retained_from);
+        }
+        previous.filter(|previous| previous != blockhash)
+    }
+}
@@ -0,0 +1,122 @@
+use {
+    crate::{report::Report, source::ReferenceBlockSource},
+    log::info,
+    std::{
+        collections::BTreeSet,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    tokio::time::Instant,
+};
+
+// getBlocks rejects ranges wider than 500,000 slots
+pub const MAX_GET_BLOCKS_RANGE: u64 = 500_000;
+
+// Tracks the slots delivered by the gRPC stream so they can be reconciled
+// against the confirmed blocks RPC knows about.
+#[derive(Debug, Default)]
+pub struct GapTracker {
+    // every slot up to and including this one has been reconciled with getBlocks
+    checked_upto: Option<u64>,
+    first_seen: Option<u64>,
+    highest_seen: Option<u64>,
+    // slots seen above `checked_upto`
+    seen: BTreeSet<u64>,
+    last_check: Option<Instant>,
+}
+
+impl GapTracker {
+    // For a backfill, where blocks are expected from `first` on even if the
+    // stream's first delivered block comes later.
+    pub fn starting_at(first: u64) -> Self {
+        Self {
+            first_seen: Some(first),
+            ..Default::default()
+        }
+    }
+
+    // Returns false for slots that were already delivered (or already reconciled).
+    pub fn record(&mut self, slot: u64) -> bool {
+        self.first_seen.get_or_insert(slot);
+        self.highest_seen = self.highest_seen.max(Some(slot));
+        self.checked_upto.is_none_or(|checked| slot > checked) && self.seen.insert(slot)
+    }
+
+    pub fn highest_seen(&self) -> Option<u64> {
+        self.highest_seen
+    }
+
+    // Extends the range to reconcile up to `slot` without marking it as seen.
+    pub fn extend_to(&mut self, slot: u64) {
+        self.highest_seen = self.highest_seen.max(Some(slot));
+    }
+
+    // Returns true at most once per `interval`, starting one interval after the first call.
+    pub fn check_due(&mut self, interval: Duration) -> bool {
+        let last_check = self.last_check.get_or_insert_with(Instant::now);
+        if last_check.elapsed() < interval {
+            return false;
+        }
+        *last_check = Instant::now();
+        true
+    }
+
+    // Range of slots not yet reconciled, bounded by the highest slot seen so far.
+    pub fn pending_range(&self) -> Option<(u64, u64)> {
+        let start = self
+            .checked_upto
+            .map(|checked| checked + 1)
+            .or(self.first_seen)?;
+        let end = self.highest_seen?;
+        (start <= end).then_some((start, end))
+    }
+
+    // Marks `[.., end]` as reconciled and returns the confirmed slots never seen.
+    pub fn reconcile(&mut self, end: u64, confirmed: &[u64]) -> Vec<u64> {
+        let missing = confirmed
+            .iter()
+            .copied()
+            .filter(|slot| *slot <= end && !self.seen.contains(slot))
+            .collect();
+        self.seen = self.seen.split_off(&(end + 1));
+        self.checked_upto = Some(end);
+        missing
+    }
+}
+
+pub async fn check_gaps(
+    client: &impl ReferenceBlockSource,
+    tracker: &Arc<Mutex<GapTracker>>,
+    report: &Arc<Mutex<Report>>,
+) -> anyhow::Result<()> {
+    let Some((start, end)) = tracker.lock().unwrap().pending_range() else {
+        return Ok(());
+    };
+    // slots past the reference's tip cannot be reconciled yet
+    let end = end.min(client.get_slot().await?);
+    if start > end {
+        return Ok(());
+    }
+
+    let mut chunk_start = start;
+    while chunk_start <= end {
+        let chunk_end = end.min(chunk_start + MAX_GET_BLOCKS_RANGE - 1);
+        let confirmed = client.get_blocks(chunk_start, chunk_end).await?;
+
+        let missing = tracker.lock().unwrap().reconcile(chunk_end, &confirmed);
+        if !missing.is_empty() {
+            let mut rep = report.lock().unwrap();
+            for slot in missing {
+                info!(
+                    "MISSING slot {} → confirmed by RPC but never delivered by gRPC",
+                    slot
+                );
+                rep.record_missing(slot);
+            }
+        }
+
+        chunk_start = chunk_end + 1;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,246 @@
+use {
+    crate::{
+        rpc::{block_cleaned_up_error, block_not_available_error, slot_skipped_error},
+        source::{BlockStreamSource, Interruption, Reconnects, ReferenceBlockSource},
+    },
+    log::{error, info},
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        rpc_config::RpcBlockConfig,
+        rpc_response::UiConfirmedBlock,
+    },
+    std::{
+        collections::BTreeMap,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicBool, Ordering},
+        },
+    },
+    tokio::task::JoinHandle,
+    tonic::Code,
+    yellowstone_grpc_proto::geyser::{
+        SubscribeRequest, SubscribeUpdateBlock, subscribe_update::UpdateOneof,
+    },
+};
+
+// blocks kept behind the highest slot received (roughly ten minutes of slots),
+// older ones are answered like blocks RPC cleaned up
+const RETAINED_SLOTS: u64 = 1_500;
+
+#[derive(Debug)]
+enum ReferenceSlot {
+    Block(ReferenceBlock),
+    Skipped,
+}
+
+// The parts of a block the verification needs, signatures in block order.
+#[derive(Debug)]
+struct ReferenceBlock {
+    blockhash: String,
+    previous_blockhash: String,
+    parent_slot: u64,
+    block_time: Option<i64>,
+    block_height: Option<u64>,
+    signatures: Vec<Vec<u8>>,
+}
+
+impl ReferenceBlock {
+    fn new(block: &SubscribeUpdateBlock) -> Self {
+        let mut transactions: Vec<_> = block.transactions.iter().collect();
+        transactions.sort_by_key(|tx| tx.index);
+        Self {
+            blockhash: block.blockhash.clone(),
+            previous_blockhash: block.parent_blockhash.clone(),
+            parent_slot: block.parent_slot,
+            block_time: block.block_time.map(|time| time.timestamp),
+            block_height: block.block_height.map(|height| height.block_height),
+            signatures: transactions
+                .into_iter()
+                .map(|tx| tx.signature.clone())
+                .collect(),
+        }
+    }
+
+    fn to_ui(&self) -> UiConfirmedBlock {
+        UiConfirmedBlock {
+            previous_blockhash: self.previous_blockhash.clone(),
+            blockhash: self.blockhash.clone(),
+            parent_slot: self.parent_slot,
+            transactions: None,
+            signatures: Some(
+                self.signatures
+                    .iter()
+                    .map(|signature| bs58::encode(signature).into_string())
+                    .collect(),
+            ),
+            rewards: None,
+            num_reward_partitions: None,
+            block_time: self.block_time,
+            block_height: self.block_height,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    slots: BTreeMap<u64, ReferenceSlot>,
+    // parent of the first block received, nothing is known up to it
+    known_after: Option<u64>,
+    highest: Option<u64>,
+    // slots below this were dropped
+    retained_from: u64,
+}
+
+impl State {
+    fn insert(&mut self, block: &SubscribeUpdateBlock) {
+        let slot = block.slot;
+        if slot < self.retained_from || self.slots.contains_key(&slot) {
+            return;
+        }
+        self.known_after.get_or_insert(block.parent_slot);
+
+        // finalized blocks chain to their parent, every slot in between was skipped
+        let first_skipped = (block.parent_slot + 1)
+            .max(self.retained_from)
+            .max(slot.saturating_sub(RETAINED_SLOTS));
+        for skipped in first_skipped..slot {
+            self.slots.entry(skipped).or_insert(ReferenceSlot::Skipped);
+        }
+        self.slots
+            .insert(slot, ReferenceSlot::Block(ReferenceBlock::new(block)));
+
+        let highest = self.highest.max(Some(slot)).unwrap_or(slot);
+        self.highest = Some(highest);
+        let retained_from = highest.saturating_sub(RETAINED_SLOTS);
+        if retained_from > self.retained_from {
+            self.slots = self.slots.split_off(&retained_from);
+            self.retained_from = retained_from;
+        }
+    }
+}
+
+// A trusted Geyser endpoint as the reference, for comparing providers entirely
+// over gRPC. Its blocks are followed in the background; a block the reference
+// has not delivered yet is answered like RPC answers a block not available yet.
+// Only signatures are kept, so it does not serve --deep-compare.
+pub struct GeyserReference {
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl GeyserReference {
+    // Subscribes to `source` with `request`, which should select the same
+    // blocks as the stream under test.
+    pub fn spawn(source: impl BlockStreamSource + 'static, request: SubscribeRequest) -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let task = tokio::spawn(follow(source, request, state.clone()));
+        Self { state, task }
+    }
+}
+
+impl Drop for GeyserReference {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// Feeds the reference with the blocks of `source`, reconnecting with
+// `from_slot` replay after errors, until the reference is dropped.
+async fn follow(
+    source: impl BlockStreamSource,
+    request: SubscribeRequest,
+    state: Arc<Mutex<State>>,
+) {
+    // set when the reference refused `from_slot`, so the next attempt subscribes live
+    let skip_replay = AtomicBool::new(false);
+    let reconnects = Reconnects::new("Reference stream");
+
+    let outcome: anyhow::Result<()> = reconnects
+        .run(|| {
+            let mut request = request.clone();
+            let (source, state, skip_replay) = (&source, &state, &skip_replay);
+            let reconnects = &reconnects;
+            async move {
+                let highest = state.lock().unwrap().highest;
+                if let Some(highest) = highest
+                    && !skip_replay.swap(false, Ordering::Relaxed)
+                {
+                    request.from_slot = Some(highest + 1);
+                    info!("Reconnecting to the reference after slot {}", highest);
+                }
+
+                let mut subscription = reconnects
+                    .subscribe(source, request.clone(), None)
+                    .await
+                    .map_err(backoff::Error::transient)?;
+                loop {
+                    match subscription.next().await {
+                        Ok(update) => {
+                            if let Some(UpdateOneof::Block(block)) = update.update_oneof {
+                                subscription.received_data();
+                                state.lock().unwrap().insert(&block);
+                            }
+                        }
+                        Err(Interruption::Failed(status)) => {
+                            if request.from_slot.is_some() && status.code() == Code::InvalidArgument
+                            {
+                                skip_replay.store(true, Ordering::Relaxed);
+                            }
+                            return Err(backoff::Error::transient(status.into()));
+                        }
+                        Err(_) => break,
+                    }
+                }
+                Err(backoff::Error::transient(anyhow::anyhow!(
+                    "Reference stream ended"
+                )))
+            }
+        })
+        .await;
+    if let Err(e) = outcome {
+        error!("Reference stream stopped: {:?}", e);
+    }
+}
+
+impl ReferenceBlockSource for GeyserReference {
+    async fn get_block(
+        &self,
+        slot: u64,
+        _config: RpcBlockConfig,
+    ) -> Result<UiConfirmedBlock, ClientError> {
+        let state = self.state.lock().unwrap();
+        match state.slots.get(&slot) {
+            Some(ReferenceSlot::Block(block)) => Ok(block.to_ui()),
+            Some(ReferenceSlot::Skipped) => Err(slot_skipped_error(slot)),
+            None if state
+                .known_after
+                .is_some_and(|known_after| slot <= known_after) =>
+            {
+                Err(
+                    ClientErrorKind::Custom(format!("slot {} precedes the reference stream", slot))
+                        .into(),
+                )
+            }
+            None if slot < state.retained_from => Err(block_cleaned_up_error(slot)),
+            None => Err(block_not_available_error(slot)),
+        }
+    }
+
+    async fn get_blocks(&self, start: u64, end: u64) -> Result<Vec<u64>, ClientError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .slots
+            .range(start..=end)
+            .filter(|(_, slot)| matches!(slot, ReferenceSlot::Block(_)))
+            .map(|(slot, _)| *slot)
+            .collect())
+    }
+
+    async fn get_slot(&self) -> Result<u64, ClientError> {
+        let highest = self.state.lock().unwrap().highest;
+        highest.ok_or_else(|| {
+            ClientErrorKind::Custom("the reference stream has not delivered a block yet".into())
+                .into()
+        })
+    }
+}
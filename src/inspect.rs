@@ -0,0 +1,322 @@
+use {
+    crate::{
+        meta::TxMetaSnapshot,
+        report::{Report, SignatureDiff},
+        rpc::{block_config, get_block_with_retry},
+        source::{BlockStreamSource, Interruption, Reconnects, ReferenceBlockSource},
+    },
+    solana_transaction_status_client_types::{
+        EncodedTransaction, EncodedTransactionWithStatusMeta, UiMessage,
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        io::{self, Write},
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    yellowstone_grpc_proto::geyser::{
+        SubscribeRequest, SubscribeUpdateBlock, SubscribeUpdateTransactionInfo,
+        subscribe_update::UpdateOneof,
+    },
+};
+
+const VOTE_PROGRAM_ID: &str = "Vote111111111111111111111111111111111111111";
+
+// ordering differences printed before the rest is summarized
+const MAX_ORDERING_LINES: usize = 20;
+
+// One transaction of the inspected block, as seen by either side.
+struct TxView {
+    signature: String,
+    vote: bool,
+    failed: bool,
+    meta: Option<TxMetaSnapshot>,
+}
+
+type TxFilter = fn(&TxView) -> bool;
+
+impl TxView {
+    fn from_grpc(tx: &SubscribeUpdateTransactionInfo) -> anyhow::Result<Self> {
+        Ok(Self {
+            signature: bs58::encode(&tx.signature).into_string(),
+            vote: tx.is_vote,
+            failed: tx.meta.as_ref().is_some_and(|meta| meta.err.is_some()),
+            meta: tx
+                .meta
+                .as_ref()
+                .map(TxMetaSnapshot::from_grpc)
+                .transpose()?,
+        })
+    }
+
+    fn from_rpc(tx: &EncodedTransactionWithStatusMeta) -> anyhow::Result<Option<Self>> {
+        let EncodedTransaction::Json(ui_tx) = &tx.transaction else {
+            return Ok(None);
+        };
+        let Some(signature) = ui_tx.signatures.first() else {
+            return Ok(None);
+        };
+        // mirrors the simple vote check the validator uses for gRPC's `is_vote`
+        let vote = match &ui_tx.message {
+            UiMessage::Raw(message) => {
+                ui_tx.signatures.len() < 3
+                    && message.instructions.len() == 1
+                    && message
+                        .account_keys
+                        .get(message.instructions[0].program_id_index as usize)
+                        .is_some_and(|program| program == VOTE_PROGRAM_ID)
+            }
+            UiMessage::Parsed(_) => false,
+        };
+        Ok(Some(Self {
+            signature: signature.clone(),
+            vote,
+            failed: tx.meta.as_ref().is_some_and(|meta| meta.err.is_some()),
+            meta: tx.meta.as_ref().map(TxMetaSnapshot::from_rpc).transpose()?,
+        }))
+    }
+}
+
+// Both sides of an inspected slot, or why a side has no block.
+pub struct SlotInspection {
+    pub slot: u64,
+    grpc: Result<Vec<TxView>, String>,
+    rpc: Result<Vec<TxView>, String>,
+}
+
+impl SlotInspection {
+    // Prints a side-by-side diff of the block.
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        write_inspection(out, self.slot, &self.grpc, &self.rpc)
+    }
+}
+
+// Fetches `slot` from `source`, replayed with the blocks `request` selects,
+// and from `reference` with full transaction details.
+pub async fn inspect_slot(
+    source: &impl BlockStreamSource,
+    request: SubscribeRequest,
+    reference: &impl ReferenceBlockSource,
+    slot: u64,
+    timeout: Duration,
+    rpc_retry_timeout: Duration,
+) -> anyhow::Result<SlotInspection> {
+    let grpc = match tokio::time::timeout(timeout, fetch_grpc_block(source, request, slot)).await {
+        Ok(block) => block?
+            .map(|block| {
+                block
+                    .transactions
+                    .iter()
+                    .map(TxView::from_grpc)
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .transpose()?
+            .ok_or_else(|| "no block, the slot was skipped".to_string()),
+        Err(_) => Err(format!("no block received within {:?}", timeout)),
+    };
+
+    let rpc = fetch_rpc_block(reference, slot, rpc_retry_timeout).await?;
+    Ok(SlotInspection { slot, grpc, rpc })
+}
+
+// Replays the stream from `slot` until it reaches the block, or passes it when
+// the slot was skipped. Pings are answered so a slow replay is not dropped.
+async fn fetch_grpc_block(
+    source: &impl BlockStreamSource,
+    request: SubscribeRequest,
+    slot: u64,
+) -> anyhow::Result<Option<SubscribeUpdateBlock>> {
+    let request = SubscribeRequest {
+        from_slot: Some(slot),
+        slots: HashMap::new(),
+        ..request
+    };
+    let mut subscription = Reconnects::new("Inspection stream")
+        .subscribe(source, request, None)
+        .await?;
+
+    loop {
+        let update = match subscription.next().await {
+            Ok(update) => update,
+            Err(Interruption::Failed(status)) => return Err(status.into()),
+            Err(Interruption::Ended | Interruption::Stalled(_)) => {
+                anyhow::bail!("gRPC stream ended before reaching slot {}", slot)
+            }
+        };
+        if let Some(UpdateOneof::Block(block)) = update.update_oneof {
+            if block.slot == slot {
+                return Ok(Some(block));
+            }
+            if block.slot > slot {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+async fn fetch_rpc_block(
+    reference: &impl ReferenceBlockSource,
+    slot: u64,
+    retry_timeout: Duration,
+) -> anyhow::Result<Result<Vec<TxView>, String>> {
+    // retries are only logged here, there is no report to count them in
+    let report = Arc::new(Mutex::new(Report::default()));
+
+    let fetched =
+        get_block_with_retry(reference, slot, block_config(true), retry_timeout, &report).await;
+    // a quorum keeps disagreements until they are taken, the inspection does
+    // not show them
+    reference.take_disagreement(slot);
+    match fetched {
+        Ok(block) => Ok(Ok(block
+            .transactions
+            .iter()
+            .flatten()
+            .map(TxView::from_rpc)
+            .filter_map(Result::transpose)
+            .collect::<anyhow::Result<Vec<_>>>()?)),
+        Err((reason, e)) => Ok(Err(format!("{}: {}", reason, e))),
+    }
+}
+
+fn write_inspection(
+    out: &mut dyn Write,
+    slot: u64,
+    grpc: &Result<Vec<TxView>, String>,
+    rpc: &Result<Vec<TxView>, String>,
+) -> io::Result<()> {
+    writeln!(out, "\n============== SLOT {} ==============", slot)?;
+    let (grpc, rpc) = match (grpc, rpc) {
+        (Ok(grpc), Ok(rpc)) => (grpc, rpc),
+        (grpc, rpc) => {
+            for (side, result) in [("gRPC", grpc), ("RPC", rpc)] {
+                match result {
+                    Ok(txs) => writeln!(out, "{}: {} transactions", side, txs.len())?,
+                    Err(e) => writeln!(out, "{}: {}", side, e)?,
+                }
+            }
+            return writeln!(out, "===========================================");
+        }
+    };
+
+    let count = |txs: &[TxView], f: TxFilter| txs.iter().filter(|tx| f(tx)).count();
+    writeln!(out, "{:<16}{:>12}{:>12}", "", "gRPC", "RPC")?;
+    let rows: [(&str, TxFilter); 5] = [
+        ("Transactions", |_| true),
+        ("Vote", |tx| tx.vote),
+        ("Non-vote", |tx| !tx.vote),
+        ("Succeeded", |tx| !tx.failed),
+        ("Failed", |tx| tx.failed),
+    ];
+    for (label, f) in rows {
+        writeln!(
+            out,
+            "{:<16}{:>12}{:>12}",
+            label,
+            count(grpc, f),
+            count(rpc, f)
+        )?;
+    }
+
+    let grpc_signatures: Vec<String> = grpc.iter().map(|tx| tx.signature.clone()).collect();
+    let rpc_signatures: Vec<String> = rpc.iter().map(|tx| tx.signature.clone()).collect();
+    let diff = SignatureDiff::compute(&grpc_signatures, &rpc_signatures);
+    if !diff.is_empty() {
+        writeln!(out, "\n--- SIGNATURES ---")?;
+        for sig in &diff.missing {
+            writeln!(out, "Missing from gRPC: {}", sig)?;
+        }
+        for sig in &diff.extra {
+            writeln!(out, "Extra in gRPC: {}", sig)?;
+        }
+        for (sig, count) in &diff.duplicated {
+            writeln!(out, "Duplicated in gRPC: {} (x{})", sig, count)?;
+        }
+    }
+
+    // relative order of the transactions present on both sides
+    let grpc_set: HashSet<&str> = grpc_signatures.iter().map(String::as_str).collect();
+    let rpc_set: HashSet<&str> = rpc_signatures.iter().map(String::as_str).collect();
+    let mut seen = HashSet::new();
+    let grpc_order: Vec<&str> = grpc_signatures
+        .iter()
+        .map(String::as_str)
+        .filter(|sig| rpc_set.contains(sig) && seen.insert(*sig))
+        .collect();
+    let rpc_order: Vec<&str> = rpc_signatures
+        .iter()
+        .map(String::as_str)
+        .filter(|sig| grpc_set.contains(sig))
+        .collect();
+    let reordered: Vec<(usize, &str, &str)> = grpc_order
+        .iter()
+        .zip(&rpc_order)
+        .enumerate()
+        .filter(|(_, (grpc, rpc))| grpc != rpc)
+        .map(|(position, (grpc, rpc))| (position, *grpc, *rpc))
+        .collect();
+    if !reordered.is_empty() {
+        writeln!(out, "\n--- ORDERING ---")?;
+        writeln!(
+            out,
+            "{} of {} shared transactions at a different position",
+            reordered.len(),
+            grpc_order.len()
+        )?;
+        for (position, grpc, rpc) in reordered.iter().take(MAX_ORDERING_LINES) {
+            writeln!(out, "#{}: gRPC {} | RPC {}", position, grpc, rpc)?;
+        }
+        if reordered.len() > MAX_ORDERING_LINES {
+            writeln!(out, "... and {} more", reordered.len() - MAX_ORDERING_LINES)?;
+        }
+    }
+
+    let rpc_by_signature: HashMap<&str, &TxView> =
+        rpc.iter().map(|tx| (tx.signature.as_str(), tx)).collect();
+    let mut header = false;
+    for grpc_tx in grpc {
+        let Some(rpc_tx) = rpc_by_signature.get(grpc_tx.signature.as_str()) else {
+            continue;
+        };
+        let mut lines = vec![];
+        if grpc_tx.vote != rpc_tx.vote {
+            lines.push(format!("vote: gRPC={} RPC={}", grpc_tx.vote, rpc_tx.vote));
+        }
+        if grpc_tx.failed != rpc_tx.failed {
+            lines.push(format!(
+                "status: gRPC={} RPC={}",
+                status(grpc_tx.failed),
+                status(rpc_tx.failed)
+            ));
+        }
+        let fields = match (&grpc_tx.meta, &rpc_tx.meta) {
+            (Some(grpc_meta), Some(rpc_meta)) => grpc_meta.diff(rpc_meta),
+            (None, None) => vec![],
+            _ => vec!["meta"],
+        };
+        if !fields.is_empty() {
+            lines.push(format!("meta: {}", fields.join(", ")));
+        }
+        if lines.is_empty() {
+            continue;
+        }
+
+        if !header {
+            writeln!(out, "\n--- TRANSACTION DIFFS ---")?;
+            header = true;
+        }
+        writeln!(out, "{}:", grpc_tx.signature)?;
+        for line in lines {
+            writeln!(out, "  {}", line)?;
+        }
+    }
+
+    if diff.is_empty() && reordered.is_empty() && !header && grpc.len() == rpc.len() {
+        writeln!(out, "\nNo differences")?;
+    }
+    writeln!(out, "===========================================")
+}
+
+fn status(failed: bool) -> &'static str {
+    if failed { "failed" } else { "succeeded" }
+}
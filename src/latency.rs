@@ -0,0 +1,80 @@
+use {
+    serde::{Serialize, Serializer},
+    std::{
+        fmt,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+// Latency samples in milliseconds, serialized as their percentiles.
+#[derive(Debug, Default, Clone)]
+pub struct LatencySamples {
+    samples: Vec<i64>,
+}
+
+impl LatencySamples {
+    pub fn record(&mut self, ms: i64) {
+        self.samples.push(ms);
+    }
+
+    pub fn summary(&self) -> Option<LatencySummary> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        // nearest-rank percentile
+        let percentile = |p: usize| sorted[(sorted.len() * p).div_ceil(100).max(1) - 1];
+        Some(LatencySummary {
+            count: sorted.len(),
+            min_ms: sorted[0],
+            p50_ms: percentile(50),
+            p90_ms: percentile(90),
+            p99_ms: percentile(99),
+            max_ms: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+impl Serialize for LatencySamples {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.summary().serialize(serializer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LatencySummary {
+    pub count: usize,
+    pub min_ms: i64,
+    pub p50_ms: i64,
+    pub p90_ms: i64,
+    pub p99_ms: i64,
+    pub max_ms: i64,
+}
+
+impl fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "p50={}ms p90={}ms p99={}ms min={}ms max={}ms (n={})",
+            self.p50_ms, self.p90_ms, self.p99_ms, self.min_ms, self.max_ms, self.count
+        )
+    }
+}
+
+// How fresh the blocks of a stream are.
+#[derive(Debug, Default, Serialize)]
+pub struct Latency {
+    // block arrival on gRPC minus the block's `block_time`, which only has
+    // second precision
+    pub block_time: LatencySamples,
+    // block arrival on gRPC until RPC first served the block, queueing and retries
+    // included, less the finalization delay below finalized commitment
+    pub rpc: LatencySamples,
+}
+
+pub fn unix_ms(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
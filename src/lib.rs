@@ -0,0 +1,26 @@
+pub mod accounts;
+pub mod forks;
+pub mod gaps;
+pub mod geyser_reference;
+pub mod inspect;
+pub mod latency;
+pub mod meta;
+pub mod metrics;
+pub mod pipeline;
+pub mod providers;
+pub mod quorum;
+pub mod record;
+pub mod reference;
+pub mod report;
+pub mod rpc;
+pub mod shutdown;
+pub mod slots;
+pub mod source;
+pub mod verifier;
+pub mod window;
+
+pub use {
+    report::Report,
+    source::{BlockStreamSource, GeyserSource, ReferenceAccountSource, ReferenceBlockSource},
+    verifier::{Verifier, VerifierConfig},
+};
@@ -1,50 +1,285 @@
+mod thresholds;
+
 use {
-    backoff::{ExponentialBackoff, future::retry},
-    clap::Parser,
-    futures::{SinkExt, StreamExt},
-    log::{error, info},
-    solana_client::{
-        rpc_client::RpcClient,
-        rpc_config::{CommitmentConfig, RpcBlockConfig, TransactionDetails},
+    crate::thresholds::Thresholds,
+    clap::{Parser, Subcommand, ValueEnum},
+    futures::future::join_all,
+    log::{error, info, warn},
+    solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::CommitmentConfig},
+    solana_grpc_integrity_checker::{
+        GeyserSource, Report, Verifier, VerifierConfig,
+        accounts::{AccountReport, AccountVerifier},
+        geyser_reference::GeyserReference,
+        inspect::inspect_slot,
+        metrics,
+        providers::{ProviderComparison, ProviderReport, ProvidersReport},
+        record::{Recorder, Recording},
+        reference::{ReferenceSource, SharedReference, snapshot_rpc},
+        report::{OutputFormat, emit_report},
+        shutdown::{Shutdown, sleep_until},
+        window::run_windows,
     },
+    solana_sdk::pubkey::Pubkey,
     std::{
         collections::HashMap,
         env,
+        io::{self, Write},
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        process::ExitCode,
+        str::FromStr,
         sync::{Arc, Mutex},
         time::Duration,
     },
-    tokio::time::Instant,
-    yellowstone_grpc_client::{ClientTlsConfig, GeyserGrpcClient, Interceptor},
+    tokio::{task::JoinHandle, time::Instant},
     yellowstone_grpc_proto::geyser::{
-        CommitmentLevel, SubscribeRequest, SubscribeRequestFilterBlocks, SubscribeRequestPing,
-        subscribe_update::UpdateOneof,
+        CommitmentLevel, SubscribeRequest, SubscribeRequestFilterAccounts,
+        SubscribeRequestFilterBlocks, SubscribeRequestFilterSlots,
     },
 };
 
+// how long after arrival a block below finalized commitment is looked up on the
+// reference, a little over the 32 slots it takes to be finalized
+const FINALIZATION_DELAY: Duration = Duration::from_secs(13);
+
 type BlockFilterMap = HashMap<String, SubscribeRequestFilterBlocks>;
+type AccountFilterMap = HashMap<String, SubscribeRequestFilterAccounts>;
+type SlotFilterMap = HashMap<String, SubscribeRequestFilterSlots>;
 
 #[derive(Debug, Clone, Parser)]
 #[clap(author, version, about)]
 struct Args {
+    // repeat to compare several providers, each with its own --x-token in the same
+    // order; required unless the subcommand never subscribes
+    #[clap(long)]
+    endpoint: Vec<String>,
+    #[clap(long)]
+    x_token: Vec<String>,
+
+    // repeat to take the reference block from a majority of RPC endpoints;
+    // required unless the reference is --reference-endpoint or --rpc-fixtures
     #[clap(long)]
-    endpoint: String,
+    rpc_uri: Vec<String>,
+
+    // compare against the blocks of this trusted Geyser endpoint instead of RPC
+    #[clap(long, conflicts_with_all = ["rpc_fixtures", "deep_compare"])]
+    reference_endpoint: Option<String>,
+    #[clap(long, requires = "reference_endpoint")]
+    reference_x_token: Option<String>,
+
+    // read getBlock responses captured with `snapshot-rpc` from this directory instead of RPC
     #[clap(long)]
-    x_token: String,
+    rpc_fixtures: Option<PathBuf>,
+
+    #[clap(long, default_value = "60")]
+    duration: u64, // seconds
 
+    // run until SIGINT/SIGTERM instead of for --duration, with rolling report windows
     #[clap(long)]
-    rpc_uri: String,
+    daemon: bool,
+
+    // length of a daemon report window
+    #[clap(long, default_value = "300")]
+    window_interval: u64, // seconds
+
+    // append every daemon window summary to this file as a JSON line
+    #[clap(long)]
+    window_file: Option<PathBuf>,
+
+    // reconnect when no update arrives for this long, 0 disables the watchdog
+    #[clap(long, default_value = "60")]
+    stall_timeout: u64, // seconds
+
+    // fetch full blocks from RPC and compare every transaction's meta
+    #[clap(long)]
+    deep_compare: bool,
+
+    #[clap(long, default_value = "30")]
+    gap_check_interval: u64, // seconds
+
+    // concurrent RPC verifications
+    #[clap(long, default_value = "4")]
+    rpc_workers: usize,
+
+    // blocks buffered between the gRPC stream and the RPC workers
+    #[clap(long, default_value = "64")]
+    queue_size: usize,
+
+    // how long a getBlock is retried on transient errors before the slot is left unverified
+    #[clap(long, default_value = "60")]
+    rpc_retry_timeout: u64, // seconds
+
+    // how long in-flight RPC verifications may still run after SIGINT/SIGTERM
+    #[clap(long, default_value = "30")]
+    shutdown_timeout: u64, // seconds
+
+    // commitment of the block stream, blocks below finalized are verified once
+    // finalized and counted as rolled back when a different block was finalized
+    #[clap(long, value_enum, default_value_t = Commitment::Finalized)]
+    commitment: Commitment,
+
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+
+    // write the final report to this file instead of stdout
+    #[clap(long)]
+    report_file: Option<PathBuf>,
+
+    #[clap(flatten)]
+    thresholds: Thresholds,
+
+    // write every raw update received to this file, zstd-compressed if it ends in .zst;
+    // with several --endpoint, one file per provider numbered in --endpoint order
+    #[clap(long)]
+    record: Option<PathBuf>,
+
+    // serve Prometheus metrics on this address, e.g. 0.0.0.0:9100
+    #[clap(long)]
+    metrics_listen: Option<SocketAddr>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
 
-    #[clap(long, default_value = "60")] duration: u64, // seconds
+impl From<Commitment> for CommitmentLevel {
+    fn from(commitment: Commitment) -> Self {
+        match commitment {
+            Commitment::Processed => Self::Processed,
+            Commitment::Confirmed => Self::Confirmed,
+            Commitment::Finalized => Self::Finalized,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum Command {
+    /// Verify account updates against RPC `getMultipleAccounts` at every finalized slot
+    Accounts {
+        #[clap(long = "account")]
+        accounts: Vec<String>,
+        #[clap(long = "owner")]
+        owners: Vec<String>,
+    },
+    /// Replay a historical slot range from the provider and verify every block against RPC
+    Backfill {
+        #[clap(long)]
+        from_slot: u64,
+        #[clap(long)]
+        to_slot: u64,
+    },
+    /// Verify a stream recorded with --record instead of subscribing
+    Replay { file: PathBuf },
+    /// Capture RPC getBlock responses for a slot range as fixtures for --rpc-fixtures
+    SnapshotRpc {
+        #[clap(long)]
+        from_slot: u64,
+        #[clap(long)]
+        to_slot: u64,
+        #[clap(long)]
+        out: PathBuf,
+    },
+    /// Fetch a single slot from gRPC replay and RPC and print a side-by-side diff
+    InspectSlot {
+        slot: u64,
+        // how long to wait for the replayed stream to reach the slot
+        #[clap(long, default_value = "60")]
+        timeout: u64, // seconds
+    },
 }
 
 impl Args {
-    async fn connect(&self) -> anyhow::Result<GeyserGrpcClient<impl Interceptor>> {
-        let client = GeyserGrpcClient::build_from_shared(self.endpoint.to_owned())?
-            .x_token(Some(self.x_token.to_owned()))?
-            .tls_config(ClientTlsConfig::new().with_native_roots())?
-            .connect()
-            .await?;
-        Ok(client)
+    fn geyser_sources(&self) -> Vec<GeyserSource> {
+        self.endpoint
+            .iter()
+            .zip(&self.x_token)
+            .map(|(endpoint, x_token)| GeyserSource {
+                endpoint: endpoint.clone(),
+                x_token: Some(x_token.clone()),
+            })
+            .collect()
+    }
+
+    fn reference_source(&self) -> anyhow::Result<ReferenceSource> {
+        ReferenceSource::new(&self.rpc_uri, self.rpc_fixtures.as_deref())
+    }
+
+    // The reference for a stream of `request`: the same blocks streamed from
+    // --reference-endpoint, or RPC.
+    fn stream_reference(&self, request: &SubscribeRequest) -> anyhow::Result<ReferenceSource> {
+        let Some(endpoint) = &self.reference_endpoint else {
+            return self.reference_source();
+        };
+        let source = GeyserSource {
+            endpoint: endpoint.clone(),
+            x_token: self.reference_x_token.clone(),
+        };
+        // the reference only holds finalized blocks, whatever the stream's commitment
+        let request = SubscribeRequest {
+            commitment: Some(CommitmentLevel::Finalized as i32),
+            slots: HashMap::new(),
+            ..request.clone()
+        };
+        Ok(ReferenceSource::Geyser(GeyserReference::spawn(
+            source, request,
+        )))
+    }
+
+    fn verifier_config(&self) -> VerifierConfig {
+        VerifierConfig {
+            deep_compare: self.deep_compare,
+            rpc_retry_timeout: Duration::from_secs(self.rpc_retry_timeout),
+            rpc_workers: self.rpc_workers,
+            queue_size: self.queue_size,
+            gap_check_interval: Duration::from_secs(self.gap_check_interval),
+            stall_timeout: self.stall_timeout(),
+            shutdown_timeout: Duration::from_secs(self.shutdown_timeout),
+            commitment: self.commitment.into(),
+            finalization_delay: match self.commitment {
+                Commitment::Finalized => Duration::ZERO,
+                _ => FINALIZATION_DELAY,
+            },
+        }
+    }
+
+    fn stall_timeout(&self) -> Option<Duration> {
+        (self.stall_timeout > 0).then(|| Duration::from_secs(self.stall_timeout))
+    }
+
+    // Fails early when a provider cannot replay back to `from_slot`.
+    async fn check_replay_horizon(&self, from_slot: u64) -> anyhow::Result<()> {
+        for source in self.geyser_sources() {
+            let mut client = source.connect().await?;
+            match client.subscribe_replay_info().await {
+                Ok(info) => match info.first_available {
+                    Some(first) if first > from_slot => anyhow::bail!(
+                        "provider {} can only replay from slot {}, requested {}",
+                        source.endpoint,
+                        first,
+                        from_slot
+                    ),
+                    Some(first) => info!(
+                        "Provider {} replay horizon starts at slot {}",
+                        source.endpoint, first
+                    ),
+                    None => warn!(
+                        "Provider {} did not report its replay horizon",
+                        source.endpoint
+                    ),
+                },
+                Err(e) => warn!(
+                    "Failed to query provider {} replay horizon: {:?}",
+                    source.endpoint, e
+                ),
+            }
+        }
+        Ok(())
     }
 
     fn build_blocks_request(&self) -> SubscribeRequest {
@@ -59,148 +294,289 @@ impl Args {
             },
         );
 
+        // every status of every slot, to verify their progression next to the blocks
+        let mut slots: SlotFilterMap = HashMap::new();
+        slots.insert(
+            "client".to_string(),
+            SubscribeRequestFilterSlots {
+                filter_by_commitment: Some(false),
+                interslot_updates: Some(true),
+            },
+        );
+
         SubscribeRequest {
             blocks,
+            slots,
+            commitment: Some(CommitmentLevel::from(self.commitment) as i32),
+            ..Default::default()
+        }
+    }
+
+    fn build_accounts_request(&self, account: Vec<String>, owner: Vec<String>) -> SubscribeRequest {
+        let mut accounts: AccountFilterMap = HashMap::new();
+        accounts.insert(
+            "client".to_string(),
+            SubscribeRequestFilterAccounts {
+                account,
+                owner,
+                filters: vec![],
+                nonempty_txn_signature: None,
+            },
+        );
+
+        // finalized slot updates tell us when a slot's account writes are complete
+        let mut slots: SlotFilterMap = HashMap::new();
+        slots.insert(
+            "client".to_string(),
+            SubscribeRequestFilterSlots {
+                filter_by_commitment: Some(true),
+                interslot_updates: Some(false),
+            },
+        );
+
+        SubscribeRequest {
+            accounts,
+            slots,
             commitment: Some(CommitmentLevel::Finalized as i32),
             ..Default::default()
         }
     }
 }
 
-#[derive(Debug, Default)]
-struct Report {
-    total_blocks: u64,
-    mismatched_blocks: u64,
-    total_grpc_txs: u64,
-    total_rpc_txs: u64,
-    details: Vec<String>,
+// Streams blocks from the provider, or compares several providers, and applies
+// the thresholds to the final report.
+async fn run_blocks(
+    args: Args,
+    request: SubscribeRequest,
+    duration: Option<Duration>,
+    range: Option<(u64, u64)>,
+    shutdown: Shutdown,
+) -> anyhow::Result<ExitCode> {
+    if args.endpoint.len() > 1 {
+        let report =
+            run_providers_for_duration(args.clone(), request, duration, range, shutdown).await?;
+        return Ok(args.thresholds.exit_code_for_providers(&report.providers));
+    }
+
+    let recorder = args.record.as_deref().map(Recorder::create).transpose()?;
+    let report =
+        run_stream_for_duration(args.clone(), request, duration, range, recorder, shutdown).await?;
+    Ok(args.thresholds.exit_code(&report))
 }
 
-async fn run_stream_for_duration(args: Args, request: SubscribeRequest, duration: Duration) {
-    let report = Arc::new(Mutex::new(Report::default()));
+async fn run_stream_for_duration(
+    args: Args,
+    request: SubscribeRequest,
+    duration: Option<Duration>,
+    range: Option<(u64, u64)>,
+    recorder: Option<Recorder>,
+    mut shutdown: Shutdown,
+) -> anyhow::Result<Report> {
+    let source = args.geyser_sources().remove(0);
+    let verifier = Verifier::new(
+        args.verifier_config(),
+        args.stream_reference(&request)?,
+        range,
+    )
+    .labelled(&source.endpoint);
     let start = Instant::now();
 
-    let _: Result<(), anyhow::Error> = retry(ExponentialBackoff::default(), || {
-        let args = args.clone();
-        let request = request.clone();
-        let report = report.clone();
-        async move {
-            let mut client = args.connect().await.map_err(backoff::Error::transient)?;
-            let (mut tx, mut stream) = client
-                .subscribe()
-                .await
-                .map_err(|e| backoff::Error::transient(anyhow::anyhow!(e)))?;
-
-            tx.send(request.clone())
-                .await
-                .map_err(|e| backoff::Error::transient(anyhow::anyhow!(e)))?;
-
-            while let Some(msg) = stream.next().await {
-                if start.elapsed() >= duration {
-                    info!("Timer finished — stopping stream...");
-                    return Err(backoff::Error::permanent(anyhow::anyhow!(
-                        "timer completed"
-                    )));
-                }
-
-                let Ok(update) = msg else { break };
-
-                if let Some(UpdateOneof::Block(block)) = update.update_oneof {
-                    let slot = block.slot;
-                    // let grpc_tx_count = block.transactions.len() as u64;
-                    let grpc_tx_count = block.executed_transaction_count;
-
-                    {
-                        let mut rep = report.lock().unwrap();
-                        rep.total_blocks += 1;
-                        rep.total_grpc_txs += grpc_tx_count;
-                    }
-
-                    if let Err(e) =
-                        compare_with_rpc(&args.rpc_uri, slot, grpc_tx_count, &report).await
-                    {
-                        error!("RPC comparison error: {:?}", e);
-                    }
-                } else if let Some(UpdateOneof::Ping(_)) = update.update_oneof {
-                    let _ = tx
-                        .send(SubscribeRequest {
-                            ping: Some(SubscribeRequestPing { id: 1 }),
-                            ..Default::default()
-                        })
-                        .await;
-                }
-            }
-
-            Err(backoff::Error::transient(anyhow::anyhow!("Stream ended")))
+    let windows = args.daemon.then(|| {
+        tokio::spawn(run_windows(
+            Duration::from_secs(args.window_interval),
+            verifier.report.clone(),
+            args.window_file.clone(),
+        ))
+    });
+
+    // dropping the stream on timeout or shutdown also completes a compressed recording
+    let outcome = tokio::select! {
+        result = verifier.stream(&source, request, recorder) => result,
+        _ = sleep_until(duration.map(|duration| start + duration)) => {
+            info!("Timer finished — stopping stream...");
+            Ok(())
         }
-    })
-    .await;
+        _ = shutdown.wait() => {
+            info!("Shutdown requested — stopping stream...");
+            Ok(())
+        }
+    };
+    if let Some(windows) = windows {
+        windows.abort();
+    }
 
-    print_final_report(&report);
+    // the report covers what was verified before a permanent stream failure
+    let report = finish(&args, verifier, &mut shutdown).await;
+    outcome.map(|()| report)
 }
 
-async fn compare_with_rpc(
-    rpc_url: &str,
-    slot: u64,
-    grpc_count: u64,
-    report: &Arc<Mutex<Report>>,
-) -> anyhow::Result<()> {
-    let client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::finalized());
-
-    let block: solana_client::rpc_response::UiConfirmedBlock = client.get_block_with_config(
-        slot,
-        RpcBlockConfig {
-            encoding: None,
-            transaction_details: Some(TransactionDetails::Signatures),
-            rewards: None,
-            commitment: Some(CommitmentConfig::finalized()),
-            max_supported_transaction_version: Some(0),
-        },
-    )?;
-
-    let rpc_count = block.signatures.map_or(0, |sigs| sigs.len() as u64);
-
-    let mut rep = report.lock().unwrap();
-    rep.total_rpc_txs += rpc_count;
-
-    if grpc_count != rpc_count {
-        rep.mismatched_blocks += 1;
-
-        rep.details.push(format!(
-            "Slot {} mismatch: gRPC Tx Count={} RPC Tx Count={}",
-            slot, grpc_count, rpc_count
-        ));
+// Streams from every --endpoint at once, each verified against RPC on its own,
+// and compares the providers with each other slot by slot.
+async fn run_providers_for_duration(
+    args: Args,
+    request: SubscribeRequest,
+    duration: Option<Duration>,
+    range: Option<(u64, u64)>,
+    mut shutdown: Shutdown,
+) -> anyhow::Result<ProvidersReport> {
+    let sources = args.geyser_sources();
+    let comparison = Arc::new(Mutex::new(ProviderComparison::new(sources.len())));
+    let reference = Arc::new(SharedReference::new(
+        args.stream_reference(&request)?,
+        sources.len(),
+    ));
+    let mut verifiers = vec![];
+    let mut recorders = vec![];
+    for (provider, source) in sources.iter().enumerate() {
+        let verifier = Verifier::new(args.verifier_config(), reference.clone(), range)
+            .labelled(&source.endpoint)
+            .compare_as(provider, comparison.clone());
+        verifiers.push(verifier);
+        let path = args
+            .record
+            .as_deref()
+            .map(|path| provider_path(path, provider));
+        recorders.push(path.as_deref().map(Recorder::create).transpose()?);
+    }
+    let start = Instant::now();
 
-        info!(
-            "MISMATCH slot {} → gRPC Tx Count={} RPC Tx Count={}",
-            slot, grpc_count, rpc_count
-        );
-    } else {
-        info!("MATCH slot {} → gRPC Tx Count={} RPC Tx Count={}", slot, grpc_count, rpc_count);
+    let windows: Vec<_> = verifiers
+        .iter()
+        .filter(|_| args.daemon)
+        .map(|verifier| {
+            tokio::spawn(run_windows(
+                Duration::from_secs(args.window_interval),
+                verifier.report.clone(),
+                args.window_file.clone(),
+            ))
+        })
+        .collect();
+
+    let streams =
+        join_all(verifiers.iter().zip(&sources).zip(recorders).map(
+            |((verifier, source), recorder)| verifier.stream(source, request.clone(), recorder),
+        ));
+    let outcomes = tokio::select! {
+        outcomes = streams => outcomes,
+        _ = sleep_until(duration.map(|duration| start + duration)) => {
+            info!("Timer finished — stopping streams...");
+            vec![]
+        }
+        _ = shutdown.wait() => {
+            info!("Shutdown requested — stopping streams...");
+            vec![]
+        }
+    };
+    let failure = sources.iter().zip(outcomes).find_map(|(source, outcome)| {
+        outcome
+            .err()
+            .map(|e| e.context(format!("stream from {} stopped", source.endpoint)))
+    });
+    windows.iter().for_each(JoinHandle::abort);
+
+    // drained together, so the shutdown timeout bounds the whole drain
+    let reports = join_all(verifiers.into_iter().map(|verifier| {
+        let mut shutdown = shutdown.clone();
+        async move { verifier.finish(&mut shutdown).await }
+    }))
+    .await;
+    let stats = comparison.lock().unwrap().finish();
+    let report = ProvidersReport {
+        providers: sources
+            .into_iter()
+            .zip(reports)
+            .zip(stats)
+            .map(|((source, report), comparison)| ProviderReport {
+                endpoint: source.endpoint,
+                comparison,
+                report,
+            })
+            .collect(),
+    };
+    if let Err(e) = emit_report(&report, args.output_format, args.report_file.as_deref()) {
+        error!("Failed to write report: {:?}", e);
     }
+    failure.map_or(Ok(report), Err)
+}
 
-    Ok(())
+// The recording of the `provider`th --endpoint: `stream.bin.zst` becomes
+// `stream-0.bin.zst`, `stream-1.bin.zst` and so on.
+fn provider_path(path: &Path, provider: usize) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = match name.split_once('.') {
+        Some((stem, extensions)) => format!("{}-{}.{}", stem, provider, extensions),
+        None => format!("{}-{}", name, provider),
+    };
+    path.with_file_name(name)
 }
 
-fn print_final_report(report: &Arc<Mutex<Report>>) {
-    let rep = report.lock().unwrap();
+// Verifies account updates from the first provider against the first RPC
+// endpoint; account state is not compared across providers or a quorum.
+async fn run_accounts_for_duration(
+    args: Args,
+    request: SubscribeRequest,
+    duration: Option<Duration>,
+    mut shutdown: Shutdown,
+) -> anyhow::Result<AccountReport> {
+    let rpc =
+        RpcClient::new_with_commitment(args.rpc_uri[0].clone(), CommitmentConfig::finalized());
+    let source = args.geyser_sources().remove(0);
+    let verifier = AccountVerifier::new(rpc, args.stall_timeout()).labelled(&source.endpoint);
+    let start = Instant::now();
+
+    let outcome = tokio::select! {
+        result = verifier.stream(&source, request) => result,
+        _ = sleep_until(duration.map(|duration| start + duration)) => {
+            info!("Timer finished — stopping stream...");
+            Ok(())
+        }
+        _ = shutdown.wait() => {
+            info!("Shutdown requested — stopping stream...");
+            Ok(())
+        }
+    };
 
-    println!("\n============== FINAL REPORT ==============");
-    println!("Total Blocks Received: {}", rep.total_blocks);
-    println!("Total gRPC Tx Count: {}", rep.total_grpc_txs);
-    println!("Total RPC Tx Count: {}", rep.total_rpc_txs);
-    println!("Mismatched Blocks: {}", rep.mismatched_blocks);
+    let report = verifier.finish();
+    if let Err(e) = emit_report(&report, args.output_format, args.report_file.as_deref()) {
+        error!("Failed to write report: {:?}", e);
+    }
+    outcome.map(|()| report)
+}
 
-    if !rep.details.is_empty() {
-        println!("\n--- MISMATCH DETAILS ---");
-        for d in &rep.details {
-            println!("{}", d);
+async fn run_replay(args: Args, path: PathBuf, mut shutdown: Shutdown) -> anyhow::Result<Report> {
+    let recording = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || Recording::open(&path)).await??
+    };
+    let verifier = Verifier::new(args.verifier_config(), args.reference_source()?, None)
+        .labelled(&path.display().to_string());
+    info!("Replaying recorded stream from {}", path.display());
+
+    let outcome = tokio::select! {
+        result = verifier.replay(recording) => result,
+        _ = shutdown.wait() => {
+            info!("Shutdown requested — stopping replay...");
+            Ok(())
         }
+    };
+    let report = finish(&args, verifier, &mut shutdown).await;
+    outcome.map(|()| report)
+}
+
+// Waits for the verifier and writes the final report.
+async fn finish(
+    args: &Args,
+    verifier: Verifier<ReferenceSource>,
+    shutdown: &mut Shutdown,
+) -> Report {
+    let report = verifier.finish(shutdown).await;
+    if let Err(e) = emit_report(&report, args.output_format, args.report_file.as_deref()) {
+        error!("Failed to write report: {:?}", e);
     }
-    println!("===========================================");
+    report
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<ExitCode> {
     unsafe {
         env::set_var(
             env_logger::DEFAULT_FILTER_ENV,
@@ -210,22 +586,139 @@ fn main() -> anyhow::Result<()> {
     env_logger::init();
 
     let args = Args::parse();
-
-    let blocks_request = args.build_blocks_request();
+    anyhow::ensure!(args.rpc_workers > 0, "--rpc-workers must be at least 1");
+    anyhow::ensure!(args.queue_size > 0, "--queue-size must be at least 1");
+    anyhow::ensure!(
+        args.window_interval > 0,
+        "--window-interval must be at least 1"
+    );
+    // replay and snapshot-rpc never subscribe to a provider
+    if !matches!(
+        args.command,
+        Some(Command::Replay { .. } | Command::SnapshotRpc { .. })
+    ) {
+        anyhow::ensure!(
+            !args.endpoint.is_empty(),
+            "at least one --endpoint is required"
+        );
+    }
+    anyhow::ensure!(
+        args.x_token.len() == args.endpoint.len(),
+        "every --endpoint needs its own --x-token"
+    );
 
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?;
 
     rt.block_on(async {
-        let stream_handle = tokio::spawn(run_stream_for_duration(
-            args.clone(),
-            blocks_request,
-            Duration::from_secs(args.duration),
-        ));
+        let duration = (!args.daemon).then(|| Duration::from_secs(args.duration));
 
-        stream_handle.await?;
+        if let Some(addr) = args.metrics_listen {
+            metrics::spawn_server(addr).await?;
+        }
+        let shutdown = Shutdown::listen();
+
+        match args.command.clone() {
+            None => {
+                let blocks_request = args.build_blocks_request();
+                tokio::spawn(run_blocks(
+                    args.clone(),
+                    blocks_request,
+                    duration,
+                    None,
+                    shutdown,
+                ))
+                .await?
+            }
+            Some(Command::Backfill { from_slot, to_slot }) => {
+                anyhow::ensure!(
+                    from_slot <= to_slot,
+                    "--from-slot must not be after --to-slot"
+                );
+                args.check_replay_horizon(from_slot).await?;
+
+                let blocks_request = SubscribeRequest {
+                    from_slot: Some(from_slot),
+                    ..args.build_blocks_request()
+                };
+                tokio::spawn(run_blocks(
+                    args.clone(),
+                    blocks_request,
+                    None,
+                    Some((from_slot, to_slot)),
+                    shutdown,
+                ))
+                .await?
+            }
+            Some(Command::Replay { file }) => {
+                let report = tokio::spawn(run_replay(args.clone(), file, shutdown)).await??;
 
-        Ok(())
+                Ok(args.thresholds.exit_code(&report))
+            }
+            Some(Command::SnapshotRpc {
+                from_slot,
+                to_slot,
+                out,
+            }) => {
+                anyhow::ensure!(
+                    from_slot <= to_slot,
+                    "--from-slot must not be after --to-slot"
+                );
+                let client = ReferenceSource::new(&args.rpc_uri, None)?;
+                snapshot_rpc(
+                    &client,
+                    Duration::from_secs(args.rpc_retry_timeout),
+                    args.rpc_workers,
+                    from_slot,
+                    to_slot,
+                    &out,
+                )
+                .await?;
+
+                Ok(ExitCode::SUCCESS)
+            }
+            Some(Command::InspectSlot { slot, timeout }) => {
+                args.check_replay_horizon(slot).await?;
+                let inspection = inspect_slot(
+                    &args.geyser_sources()[0],
+                    args.build_blocks_request(),
+                    &args.reference_source()?,
+                    slot,
+                    Duration::from_secs(timeout),
+                    Duration::from_secs(args.rpc_retry_timeout),
+                )
+                .await?;
+                let mut out = io::stdout().lock();
+                inspection.write_text(&mut out)?;
+                out.flush()?;
+
+                Ok(ExitCode::SUCCESS)
+            }
+            Some(Command::Accounts { accounts, owners }) => {
+                anyhow::ensure!(
+                    !accounts.is_empty() || !owners.is_empty(),
+                    "at least one --account or --owner is required"
+                );
+                anyhow::ensure!(
+                    !args.rpc_uri.is_empty(),
+                    "accounts are verified against RPC, --rpc-uri is required"
+                );
+                for key in accounts.iter().chain(&owners) {
+                    Pubkey::from_str(key)
+                        .map_err(|e| anyhow::anyhow!("invalid pubkey {}: {}", key, e))?;
+                }
+                let accounts_request = args.build_accounts_request(accounts, owners);
+                let report = tokio::spawn(run_accounts_for_duration(
+                    args.clone(),
+                    accounts_request,
+                    duration,
+                    shutdown,
+                ))
+                .await??;
+
+                Ok(args.thresholds.exit_code_for_accounts(&report))
+            }
+        }
     })
 }
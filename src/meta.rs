@@ -0,0 +1,274 @@
+use {
+    base64::{Engine, engine::general_purpose::STANDARD},
+    serde::Serialize,
+    serde_json::Value,
+    solana_transaction_status_client_types::{
+        UiInstruction, UiLoadedAddresses, UiTransactionReturnData, UiTransactionStatusMeta,
+        UiTransactionTokenBalance,
+    },
+    yellowstone_grpc_proto::{
+        convert_from,
+        prelude::{InnerInstructions, TokenBalance, TransactionStatusMeta},
+    },
+};
+
+// Encoding-neutral view of a transaction's status meta, so the protobuf meta
+// emitted by gRPC and the JSON meta returned by RPC `getBlock` can be compared
+// field by field.
+#[derive(Debug, PartialEq)]
+pub struct TxMetaSnapshot {
+    pub fee: u64,
+    pub err: Option<Value>,
+    pub pre_balances: Vec<u64>,
+    pub post_balances: Vec<u64>,
+    pub pre_token_balances: Vec<TokenBalanceSnapshot>,
+    pub post_token_balances: Vec<TokenBalanceSnapshot>,
+    pub log_messages: Option<Vec<String>>,
+    pub inner_instructions: Option<Vec<InnerInstructionsSnapshot>>,
+    pub loaded_writable_addresses: Vec<String>,
+    pub loaded_readonly_addresses: Vec<String>,
+    pub return_data: Option<(String, Vec<u8>)>,
+    pub compute_units_consumed: Option<u64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TokenBalanceSnapshot {
+    pub account_index: u32,
+    pub mint: String,
+    pub owner: String,
+    pub program_id: String,
+    pub amount: String,
+    pub decimals: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InnerInstructionsSnapshot {
+    pub index: u32,
+    pub instructions: Vec<InnerInstructionSnapshot>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InnerInstructionSnapshot {
+    pub program_id_index: u32,
+    pub accounts: Vec<u8>,
+    pub data: Vec<u8>,
+    pub stack_height: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetaDiff {
+    pub signature: String,
+    pub fields: Vec<&'static str>,
+}
+
+impl TxMetaSnapshot {
+    pub fn from_grpc(meta: &TransactionStatusMeta) -> anyhow::Result<Self> {
+        let err = convert_from::create_tx_error(meta.err.as_ref())
+            .map_err(anyhow::Error::msg)?
+            .map(serde_json::to_value)
+            .transpose()?;
+
+        Ok(Self {
+            fee: meta.fee,
+            err,
+            pre_balances: meta.pre_balances.clone(),
+            post_balances: meta.post_balances.clone(),
+            pre_token_balances: meta
+                .pre_token_balances
+                .iter()
+                .map(grpc_token_balance)
+                .collect(),
+            post_token_balances: meta
+                .post_token_balances
+                .iter()
+                .map(grpc_token_balance)
+                .collect(),
+            log_messages: (!meta.log_messages_none).then(|| meta.log_messages.clone()),
+            inner_instructions: (!meta.inner_instructions_none).then(|| {
+                meta.inner_instructions
+                    .iter()
+                    .map(grpc_inner_instructions)
+                    .collect()
+            }),
+            loaded_writable_addresses: meta
+                .loaded_writable_addresses
+                .iter()
+                .map(|key| bs58::encode(key).into_string())
+                .collect(),
+            loaded_readonly_addresses: meta
+                .loaded_readonly_addresses
+                .iter()
+                .map(|key| bs58::encode(key).into_string())
+                .collect(),
+            return_data: if meta.return_data_none {
+                None
+            } else {
+                meta.return_data
+                    .as_ref()
+                    .map(|rd| (bs58::encode(&rd.program_id).into_string(), rd.data.clone()))
+            },
+            compute_units_consumed: meta.compute_units_consumed,
+        })
+    }
+
+    pub fn from_rpc(meta: &UiTransactionStatusMeta) -> anyhow::Result<Self> {
+        let err = meta.err.as_ref().map(serde_json::to_value).transpose()?;
+
+        let pre_token_balances: Option<Vec<UiTransactionTokenBalance>> =
+            meta.pre_token_balances.clone().into();
+        let post_token_balances: Option<Vec<UiTransactionTokenBalance>> =
+            meta.post_token_balances.clone().into();
+
+        let inner_instructions = Option::<Vec<_>>::from(meta.inner_instructions.clone())
+            .map(|inner| {
+                inner
+                    .into_iter()
+                    .map(|ixs| -> anyhow::Result<InnerInstructionsSnapshot> {
+                        let instructions = ixs
+                            .instructions
+                            .into_iter()
+                            .map(|ix| -> anyhow::Result<InnerInstructionSnapshot> {
+                                match ix {
+                                    UiInstruction::Compiled(ix) => Ok(InnerInstructionSnapshot {
+                                        program_id_index: ix.program_id_index as u32,
+                                        accounts: ix.accounts,
+                                        data: bs58::decode(&ix.data).into_vec()?,
+                                        stack_height: ix.stack_height,
+                                    }),
+                                    UiInstruction::Parsed(_) => {
+                                        anyhow::bail!("parsed inner instructions are not supported")
+                                    }
+                                }
+                            })
+                            .collect::<anyhow::Result<Vec<_>>>()?;
+                        Ok(InnerInstructionsSnapshot {
+                            index: ixs.index as u32,
+                            instructions,
+                        })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .transpose()?;
+
+        let loaded_addresses: Option<UiLoadedAddresses> = meta.loaded_addresses.clone().into();
+        let (loaded_writable_addresses, loaded_readonly_addresses) = loaded_addresses
+            .map(|addresses| (addresses.writable, addresses.readonly))
+            .unwrap_or_default();
+
+        let return_data: Option<UiTransactionReturnData> = meta.return_data.clone().into();
+        let return_data = return_data
+            .map(|rd| {
+                STANDARD
+                    .decode(&rd.data.0)
+                    .map(|data| (rd.program_id, data))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            fee: meta.fee,
+            err,
+            pre_balances: meta.pre_balances.clone(),
+            post_balances: meta.post_balances.clone(),
+            pre_token_balances: pre_token_balances
+                .unwrap_or_default()
+                .iter()
+                .map(rpc_token_balance)
+                .collect(),
+            post_token_balances: post_token_balances
+                .unwrap_or_default()
+                .iter()
+                .map(rpc_token_balance)
+                .collect(),
+            log_messages: meta.log_messages.clone().into(),
+            inner_instructions,
+            loaded_writable_addresses,
+            loaded_readonly_addresses,
+            return_data,
+            compute_units_consumed: meta.compute_units_consumed.clone().into(),
+        })
+    }
+
+    // Names of the fields that differ between the two snapshots.
+    pub fn diff(&self, other: &Self) -> Vec<&'static str> {
+        let mut fields = vec![];
+        if self.fee != other.fee {
+            fields.push("fee");
+        }
+        if self.err != other.err {
+            fields.push("err");
+        }
+        if self.pre_balances != other.pre_balances {
+            fields.push("pre_balances");
+        }
+        if self.post_balances != other.post_balances {
+            fields.push("post_balances");
+        }
+        if self.pre_token_balances != other.pre_token_balances {
+            fields.push("pre_token_balances");
+        }
+        if self.post_token_balances != other.post_token_balances {
+            fields.push("post_token_balances");
+        }
+        if self.log_messages != other.log_messages {
+            fields.push("log_messages");
+        }
+        if self.inner_instructions != other.inner_instructions {
+            fields.push("inner_instructions");
+        }
+        if self.loaded_writable_addresses != other.loaded_writable_addresses
+            || self.loaded_readonly_addresses != other.loaded_readonly_addresses
+        {
+            fields.push("loaded_addresses");
+        }
+        if self.return_data != other.return_data {
+            fields.push("return_data");
+        }
+        if self.compute_units_consumed != other.compute_units_consumed {
+            fields.push("compute_units_consumed");
+        }
+        fields
+    }
+}
+
+fn grpc_token_balance(balance: &TokenBalance) -> TokenBalanceSnapshot {
+    let (amount, decimals) = balance
+        .ui_token_amount
+        .as_ref()
+        .map(|amount| (amount.amount.clone(), amount.decimals))
+        .unwrap_or_default();
+    TokenBalanceSnapshot {
+        account_index: balance.account_index,
+        mint: balance.mint.clone(),
+        owner: balance.owner.clone(),
+        program_id: balance.program_id.clone(),
+        amount,
+        decimals,
+    }
+}
+
+fn rpc_token_balance(balance: &UiTransactionTokenBalance) -> TokenBalanceSnapshot {
+    TokenBalanceSnapshot {
+        account_index: balance.account_index as u32,
+        mint: balance.mint.clone(),
+        owner: Option::from(balance.owner.clone()).unwrap_or_default(),
+        program_id: Option::from(balance.program_id.clone()).unwrap_or_default(),
+        amount: balance.ui_token_amount.amount.clone(),
+        decimals: balance.ui_token_amount.decimals as u32,
+    }
+}
+
+fn grpc_inner_instructions(ixs: &InnerInstructions) -> InnerInstructionsSnapshot {
+    InnerInstructionsSnapshot {
+        index: ixs.index,
+        instructions: ixs
+            .instructions
+            .iter()
+            .map(|ix| InnerInstructionSnapshot {
+                program_id_index: ix.program_id_index,
+                accounts: ix.accounts.clone(),
+                data: ix.data.clone(),
+                stack_height: ix.stack_height,
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,315 @@
+use {
+    crate::slots::SlotAnomalyKind,
+    log::{error, info},
+    prometheus::{
+        Encoder, HistogramVec, IntCounterVec, IntGaugeVec, TextEncoder, register_histogram_vec,
+        register_int_counter_vec, register_int_gauge_vec,
+    },
+    std::{net::SocketAddr, sync::LazyLock},
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    },
+    yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof,
+};
+
+pub static BLOCKS_RECEIVED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_blocks_received_total",
+        "Blocks received from the gRPC stream",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static BLOCKS_VERIFIED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_blocks_verified_total",
+        "Blocks compared against RPC getBlock",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static MISMATCHED_BLOCKS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_mismatched_blocks_total",
+        "Blocks whose gRPC contents differ from RPC getBlock",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static MISSING_BLOCKS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_missing_blocks_total",
+        "Blocks confirmed by RPC getBlocks but never delivered by gRPC",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static UNVERIFIED_SLOTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_unverified_slots_total",
+        "Slots received from gRPC that RPC could not serve",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static DUPLICATE_BLOCKS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_duplicate_blocks_total",
+        "Blocks delivered more than once by gRPC",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static ROLLED_BACK_BLOCKS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_rolled_back_blocks_total",
+        "Blocks received below finalized commitment that were never finalized",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static DUPLICATE_SLOTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_duplicate_slots_total",
+        "Slots received again with a different blockhash",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static RPC_DISAGREEMENTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_rpc_disagreements_total",
+        "Slots the RPC endpoints of a quorum answered differently",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static RPC_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_rpc_errors_total",
+        "Failed RPC getBlock attempts, retried or not",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static RECONNECTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_reconnects_total",
+        "gRPC stream reconnects",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static STALLS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_stalls_total",
+        "Reconnects forced by the stall watchdog",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+pub static STREAM_MESSAGES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_stream_messages_total",
+        "Messages received from the gRPC stream by kind",
+        &["provider", "kind"]
+    )
+    .unwrap()
+});
+
+pub static SLOT_STATUS_ANOMALIES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "grpc_integrity_slot_status_anomalies_total",
+        "Slot status stream anomalies by kind",
+        &["provider", "kind"]
+    )
+    .unwrap()
+});
+
+pub static GRPC_TO_RPC_LAG: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "grpc_integrity_grpc_to_rpc_lag_seconds",
+        "Time from receiving a block on gRPC until RPC getBlock served it, less the finalization delay",
+        &["provider"],
+        vec![0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0]
+    )
+    .unwrap()
+});
+
+pub static BLOCK_TIME_LAG: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "grpc_integrity_block_time_lag_seconds",
+        "Time from a block's block_time until it was received on gRPC",
+        &["provider"],
+        vec![0.5, 1.0, 2.0, 5.0, 10.0, 15.0, 20.0, 30.0, 60.0]
+    )
+    .unwrap()
+});
+
+pub static QUEUE_DEPTH: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "grpc_integrity_verification_queue_depth",
+        "Blocks waiting for an RPC worker",
+        &["provider"]
+    )
+    .unwrap()
+});
+
+// every kind `record_stream_message` counts
+const STREAM_MESSAGE_KINDS: [&str; 10] = [
+    "account",
+    "slot",
+    "transaction",
+    "transaction_status",
+    "block",
+    "ping",
+    "pong",
+    "block_meta",
+    "entry",
+    "unknown",
+];
+
+// Registers every metric family, so `/metrics` serves them from the start.
+fn init() {
+    for counter in [
+        &BLOCKS_RECEIVED,
+        &BLOCKS_VERIFIED,
+        &MISMATCHED_BLOCKS,
+        &MISSING_BLOCKS,
+        &UNVERIFIED_SLOTS,
+        &DUPLICATE_BLOCKS,
+        &ROLLED_BACK_BLOCKS,
+        &DUPLICATE_SLOTS,
+        &RPC_DISAGREEMENTS,
+        &RPC_ERRORS,
+        &RECONNECTS,
+        &STALLS,
+        &STREAM_MESSAGES,
+        &SLOT_STATUS_ANOMALIES,
+    ] {
+        LazyLock::force(counter);
+    }
+    LazyLock::force(&GRPC_TO_RPC_LAG);
+    LazyLock::force(&BLOCK_TIME_LAG);
+    LazyLock::force(&QUEUE_DEPTH);
+}
+
+// Creates every series of `provider` at zero, so `/metrics` lists them all
+// before its first update arrives.
+pub fn init_provider(provider: &str) {
+    for counter in [
+        &BLOCKS_RECEIVED,
+        &BLOCKS_VERIFIED,
+        &MISMATCHED_BLOCKS,
+        &MISSING_BLOCKS,
+        &UNVERIFIED_SLOTS,
+        &DUPLICATE_BLOCKS,
+        &ROLLED_BACK_BLOCKS,
+        &DUPLICATE_SLOTS,
+        &RPC_DISAGREEMENTS,
+        &RPC_ERRORS,
+        &RECONNECTS,
+        &STALLS,
+    ] {
+        counter.with_label_values(&[provider]);
+    }
+    for kind in STREAM_MESSAGE_KINDS {
+        STREAM_MESSAGES.with_label_values(&[provider, kind]);
+    }
+    for kind in [
+        SlotAnomalyKind::SkippedStatus,
+        SlotAnomalyKind::Regression,
+        SlotAnomalyKind::Undelivered,
+    ] {
+        SLOT_STATUS_ANOMALIES.with_label_values(&[provider, &kind.to_string()]);
+    }
+    GRPC_TO_RPC_LAG.with_label_values(&[provider]);
+    BLOCK_TIME_LAG.with_label_values(&[provider]);
+    QUEUE_DEPTH.with_label_values(&[provider]);
+}
+
+pub fn record_stream_message(provider: &str, update: Option<&UpdateOneof>) {
+    let kind = match update {
+        Some(UpdateOneof::Account(_)) => "account",
+        Some(UpdateOneof::Slot(_)) => "slot",
+        Some(UpdateOneof::Transaction(_)) => "transaction",
+        Some(UpdateOneof::TransactionStatus(_)) => "transaction_status",
+        Some(UpdateOneof::Block(_)) => "block",
+        Some(UpdateOneof::Ping(_)) => "ping",
+        Some(UpdateOneof::Pong(_)) => "pong",
+        Some(UpdateOneof::BlockMeta(_)) => "block_meta",
+        Some(UpdateOneof::Entry(_)) => "entry",
+        None => "unknown",
+    };
+    STREAM_MESSAGES.with_label_values(&[provider, kind]).inc();
+}
+
+// Binds the exporter and serves `/metrics` in the background.
+pub async fn spawn_server(addr: SocketAddr) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    init();
+    info!("Serving Prometheus metrics on http://{}/metrics", addr);
+
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    error!("Metrics accept error: {:?}", e);
+                    continue;
+                }
+            };
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream).await {
+                    error!("Metrics request error: {:?}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+async fn handle_connection(mut stream: TcpStream) -> anyhow::Result<()> {
+    // only the request line matters, the rest of the request is ignored
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, content_type, body) = if path == "/metrics" {
+        let encoder = TextEncoder::new();
+        let mut body = vec![];
+        encoder.encode(&prometheus::gather(), &mut body)?;
+        ("200 OK", encoder.format_type().to_string(), body)
+    } else {
+        (
+            "404 Not Found",
+            "text/plain".to_string(),
+            b"not found\n".to_vec(),
+        )
+    };
+
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,251 @@
+use {
+    crate::{
+        metrics,
+        report::{Report, duration_ms},
+        shutdown::{Shutdown, sleep_until},
+        source::ReferenceBlockSource,
+        verifier::{VerifierConfig, compare_with_rpc},
+    },
+    futures::future::join_all,
+    log::{info, warn},
+    serde::Serialize,
+    std::{
+        collections::VecDeque,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+        time::{Duration, SystemTime},
+    },
+    tokio::{
+        sync::mpsc::{self, Receiver, Sender, error::TrySendError},
+        task::JoinHandle,
+        time::Instant,
+    },
+    yellowstone_grpc_proto::geyser::{CommitmentLevel, SubscribeUpdateTransactionInfo},
+};
+
+// A block received from gRPC, waiting to be verified against RPC.
+#[derive(Debug)]
+pub struct VerifyJob {
+    pub slot: u64,
+    pub grpc_count: u64,
+    pub transactions: Vec<SubscribeUpdateTransactionInfo>,
+    pub blockhash: String,
+    pub enqueued_at: Instant,
+    // wall-clock arrival on the stream, unknown for replayed recordings
+    pub received_at: Option<SystemTime>,
+    // the block's own timestamp, in unix seconds
+    pub block_time: Option<i64>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BackpressureStats {
+    pub workers: usize,
+    pub queue_capacity: usize,
+    pub max_queue_depth: usize,
+    // number of times the stream loop found the queue full and had to wait
+    pub queue_full_events: u64,
+    #[serde(rename = "enqueue_wait_total_ms", serialize_with = "duration_ms")]
+    pub enqueue_wait_total: Duration,
+    #[serde(rename = "enqueue_wait_max_ms", serialize_with = "duration_ms")]
+    pub enqueue_wait_max: Duration,
+    // longest time a block sat in the queue before a worker picked it up
+    #[serde(rename = "queue_wait_max_ms", serialize_with = "duration_ms")]
+    pub queue_wait_max: Duration,
+}
+
+// The RPC workers and what is left for them to verify.
+pub struct Workers {
+    // the workers, then the delay stage if any
+    handles: Vec<JoinHandle<()>>,
+    // the workers' queue, then the delay stage's if any
+    queues: Vec<Arc<tokio::sync::Mutex<Receiver<VerifyJob>>>>,
+    // blocks held by the delay stage, or picked up by a worker but not recorded yet
+    in_flight: Arc<AtomicU64>,
+}
+
+// Spawns `count` workers verifying the blocks received on `jobs`. Below
+// finalized commitment, a delay stage in front of them holds every block for
+// the finalization delay, so no worker sits idle waiting for finalization.
+pub fn spawn_workers<R: ReferenceBlockSource>(
+    count: usize,
+    jobs: Receiver<VerifyJob>,
+    reference: Arc<R>,
+    config: VerifierConfig,
+    report: Arc<Mutex<Report>>,
+) -> Workers {
+    let in_flight = Arc::new(AtomicU64::new(0));
+    let delay = Some(config.finalization_delay)
+        .filter(|delay| config.commitment != CommitmentLevel::Finalized && !delay.is_zero());
+    let (jobs, delay_stage) = match delay {
+        Some(delay) => {
+            let (delayed, delayed_rx) = mpsc::channel(config.queue_size);
+            let held = Arc::new(tokio::sync::Mutex::new(jobs));
+            let stage = tokio::spawn(run_delay_stage(
+                delay,
+                held.clone(),
+                delayed,
+                in_flight.clone(),
+            ));
+            (delayed_rx, Some((stage, held)))
+        }
+        None => (jobs, None),
+    };
+    let jobs = Arc::new(tokio::sync::Mutex::new(jobs));
+    let mut handles: Vec<_> = (0..count)
+        .map(|_| {
+            let jobs = jobs.clone();
+            let in_flight = in_flight.clone();
+            let reference = reference.clone();
+            let report = report.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (job, depth) = {
+                        let mut jobs = jobs.lock().await;
+                        let job = jobs.recv().await;
+                        if job.is_some() {
+                            in_flight.fetch_add(1, Ordering::Relaxed);
+                        }
+                        (job, jobs.len())
+                    };
+                    let Some(job) = job else {
+                        break;
+                    };
+
+                    {
+                        let mut rep = report.lock().unwrap();
+                        metrics::QUEUE_DEPTH
+                            .with_label_values(&[&rep.provider])
+                            .set(depth as i64);
+                        let waited = job.enqueued_at.elapsed();
+                        rep.backpressure.queue_wait_max =
+                            rep.backpressure.queue_wait_max.max(waited);
+                    }
+
+                    compare_with_rpc(&*reference, &job, config, &report).await;
+                    in_flight.fetch_sub(1, Ordering::Relaxed);
+                }
+            })
+        })
+        .collect();
+    let mut queues = vec![jobs];
+    if let Some((stage, held)) = delay_stage {
+        handles.push(stage);
+        queues.push(held);
+    }
+    Workers {
+        handles,
+        queues,
+        in_flight,
+    }
+}
+
+// Forwards every block to the workers `delay` after it was enqueued. The delay
+// is the same for every block, so they come due in arrival order.
+async fn run_delay_stage(
+    delay: Duration,
+    jobs: Arc<tokio::sync::Mutex<Receiver<VerifyJob>>>,
+    workers: Sender<VerifyJob>,
+    in_flight: Arc<AtomicU64>,
+) {
+    let mut jobs = jobs.lock().await;
+    let mut held = VecDeque::new();
+    let mut open = true;
+    while open || !held.is_empty() {
+        let due = held.front().map(|job: &VerifyJob| job.enqueued_at + delay);
+        tokio::select! {
+            job = jobs.recv(), if open => match job {
+                Some(job) => {
+                    in_flight.fetch_add(1, Ordering::Relaxed);
+                    held.push_back(job);
+                }
+                None => open = false,
+            },
+            _ = sleep_until(due) => {
+                let mut job = held.pop_front().unwrap();
+                // the time spent in the workers' queue is measured from here
+                job.enqueued_at = Instant::now();
+                let sent = workers.send(job).await;
+                in_flight.fetch_sub(1, Ordering::Relaxed);
+                if sent.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Waits for the workers to finish the queued blocks. After a shutdown signal
+// they only get `timeout` more, then the remaining verifications are dropped.
+// Returns the number of blocks dropped, queued or in flight.
+pub async fn drain_workers(workers: Workers, shutdown: &mut Shutdown, timeout: Duration) -> u64 {
+    let aborts: Vec<_> = workers
+        .handles
+        .iter()
+        .map(JoinHandle::abort_handle)
+        .collect();
+    let drain = join_all(workers.handles);
+    tokio::pin!(drain);
+
+    tokio::select! {
+        _ = &mut drain => 0,
+        _ = shutdown.wait() => {
+            info!("Draining in-flight RPC verifications (up to {:?})...", timeout);
+            if tokio::time::timeout(timeout, &mut drain).await.is_ok() {
+                return 0;
+            }
+            aborts.iter().for_each(|worker| worker.abort());
+            // aborted workers release the queues once they have stopped
+            drain.await;
+            let mut queued = 0;
+            for queue in &workers.queues {
+                queued += queue.lock().await.len() as u64;
+            }
+            let abandoned = queued + workers.in_flight.load(Ordering::Relaxed);
+            warn!(
+                "Shutdown timeout reached, abandoned {} queued and in-flight RPC verifications",
+                abandoned
+            );
+            abandoned
+        }
+    }
+}
+
+// Hands a job to the workers, waiting for room in the queue when they fall behind.
+pub async fn enqueue(
+    jobs: &Sender<VerifyJob>,
+    job: VerifyJob,
+    report: &Arc<Mutex<Report>>,
+) -> anyhow::Result<()> {
+    let job = match jobs.try_send(job) {
+        Ok(()) => {
+            let depth = jobs.max_capacity() - jobs.capacity();
+            let mut rep = report.lock().unwrap();
+            metrics::QUEUE_DEPTH
+                .with_label_values(&[&rep.provider])
+                .set(depth as i64);
+            rep.backpressure.max_queue_depth = rep.backpressure.max_queue_depth.max(depth);
+            return Ok(());
+        }
+        Err(TrySendError::Full(job)) => job,
+        Err(TrySendError::Closed(_)) => anyhow::bail!("verification queue closed"),
+    };
+
+    let waiting = Instant::now();
+    jobs.send(job)
+        .await
+        .map_err(|_| anyhow::anyhow!("verification queue closed"))?;
+    let waited = waiting.elapsed();
+
+    let mut rep = report.lock().unwrap();
+    metrics::QUEUE_DEPTH
+        .with_label_values(&[&rep.provider])
+        .set(jobs.max_capacity() as i64);
+    let stats = &mut rep.backpressure;
+    stats.max_queue_depth = stats.queue_capacity;
+    stats.queue_full_events += 1;
+    stats.enqueue_wait_total += waited;
+    stats.enqueue_wait_max = stats.enqueue_wait_max.max(waited);
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use {
+    crate::{
+        latency::LatencySamples,
+        report::{RenderReport, Report, duration_ms},
+    },
+    serde::Serialize,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        hash::{DefaultHasher, Hash, Hasher},
+        io::{self, Write},
+        mem,
+        time::Duration,
+    },
+    tokio::time::Instant,
+    yellowstone_grpc_proto::geyser::SubscribeUpdateBlock,
+};
+
+// how far behind the highest slot seen a slot is compared, even if some
+// providers have not delivered it (roughly a minute of slots)
+const SETTLE_SLOTS: u64 = 150;
+
+#[derive(Debug, Clone, Copy)]
+struct Arrival {
+    at: Instant,
+    // hash of the block's transaction count and signatures, in order
+    fingerprint: u64,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ProviderStats {
+    // slots compared with the other providers
+    pub delivered: u64,
+    // slots this provider delivered before every other one
+    pub first: u64,
+    // slots another provider delivered but this one did not
+    pub absent: u64,
+    // slots delivered too late to be compared
+    pub late: u64,
+    // slots delivered again with another blockhash after they were compared,
+    // left out of the comparison
+    pub replaced: u64,
+    // slots whose contents differ from what most providers delivered
+    pub disagreements: u64,
+    // time behind the fastest provider
+    #[serde(rename = "lag_total_ms", serialize_with = "duration_ms")]
+    pub lag_total: Duration,
+    #[serde(rename = "lag_max_ms", serialize_with = "duration_ms")]
+    pub lag_max: Duration,
+    pub lag: LatencySamples,
+}
+
+impl ProviderStats {
+    pub fn lag_avg(&self) -> Duration {
+        if self.delivered == 0 {
+            return Duration::ZERO;
+        }
+        self.lag_total / self.delivered as u32
+    }
+}
+
+// Compares the blocks of several providers slot by slot: who delivered a slot
+// first, who never delivered it and who delivered different contents.
+#[derive(Debug)]
+pub struct ProviderComparison {
+    providers: usize,
+    pending: BTreeMap<u64, Vec<Option<Arrival>>>,
+    // slots below this are settled, later arrivals are counted as late
+    settled_below: u64,
+    // slots at or above settled_below that every provider already delivered
+    settled: BTreeSet<u64>,
+    highest_seen: u64,
+    stats: Vec<ProviderStats>,
+}
+
+impl ProviderComparison {
+    pub fn new(providers: usize) -> Self {
+        Self {
+            providers,
+            pending: BTreeMap::new(),
+            settled_below: 0,
+            settled: BTreeSet::new(),
+            highest_seen: 0,
+            stats: vec![ProviderStats::default(); providers],
+        }
+    }
+
+    // `replaced` is set when the provider already delivered the slot with
+    // another blockhash, e.g. on a fork.
+    pub fn record(&mut self, provider: usize, block: &SubscribeUpdateBlock, replaced: bool) {
+        let slot = block.slot;
+        if slot < self.settled_below || self.settled.contains(&slot) {
+            let stats = &mut self.stats[provider];
+            if replaced {
+                stats.replaced += 1;
+            } else {
+                stats.late += 1;
+            }
+            return;
+        }
+
+        let arrivals = self
+            .pending
+            .entry(slot)
+            .or_insert_with(|| vec![None; self.providers]);
+        arrivals[provider] = Some(Arrival {
+            at: Instant::now(),
+            fingerprint: fingerprint(block),
+        });
+        if arrivals.iter().all(Option::is_some)
+            && let Some(arrivals) = self.pending.remove(&slot)
+        {
+            self.settle(&arrivals);
+            self.settled.insert(slot);
+        }
+
+        self.highest_seen = self.highest_seen.max(slot);
+        let cutoff = self.highest_seen.saturating_sub(SETTLE_SLOTS);
+        if cutoff > self.settled_below {
+            let pending = self.pending.split_off(&cutoff);
+            for arrivals in mem::replace(&mut self.pending, pending).values() {
+                self.settle(arrivals);
+            }
+            self.settled = self.settled.split_off(&cutoff);
+            self.settled_below = cutoff;
+        }
+    }
+
+    // Settles the slots still pending and returns the stats of every provider.
+    pub fn finish(&mut self) -> Vec<ProviderStats> {
+        for arrivals in mem::take(&mut self.pending).values() {
+            self.settle(arrivals);
+        }
+        self.stats.clone()
+    }
+
+    fn settle(&mut self, arrivals: &[Option<Arrival>]) {
+        let Some(fastest) = arrivals.iter().flatten().map(|arrival| arrival.at).min() else {
+            return;
+        };
+        let mut fingerprints: BTreeMap<u64, usize> = BTreeMap::new();
+        for arrival in arrivals.iter().flatten() {
+            *fingerprints.entry(arrival.fingerprint).or_default() += 1;
+        }
+        let mut counts: Vec<(u64, usize)> = fingerprints.into_iter().collect();
+        counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        // with a tie there is no majority, and every provider disagrees
+        let majority = match counts.as_slice() {
+            [(_, first), (_, second), ..] if first == second => None,
+            [(fingerprint, _), ..] => Some(*fingerprint),
+            [] => None,
+        };
+
+        for (stats, arrival) in self.stats.iter_mut().zip(arrivals) {
+            let Some(arrival) = arrival else {
+                stats.absent += 1;
+                continue;
+            };
+            let lag = arrival.at - fastest;
+            stats.delivered += 1;
+            stats.lag_total += lag;
+            stats.lag_max = stats.lag_max.max(lag);
+            stats.lag.record(lag.as_millis() as i64);
+            if lag.is_zero() {
+                stats.first += 1;
+            }
+            if majority != Some(arrival.fingerprint) {
+                stats.disagreements += 1;
+            }
+        }
+    }
+}
+
+fn fingerprint(block: &SubscribeUpdateBlock) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    block.executed_transaction_count.hash(&mut hasher);
+    for tx in &block.transactions {
+        tx.signature.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProviderReport {
+    pub endpoint: String,
+    pub comparison: ProviderStats,
+    pub report: Report,
+}
+
+impl ProviderReport {
+    // blocks delivered out of the blocks RPC confirmed, as a percentage
+    pub fn completeness(&self) -> f64 {
+        let expected = self.report.total_blocks + self.report.missing_blocks;
+        if expected == 0 {
+            return 100.0;
+        }
+        self.report.total_blocks as f64 * 100.0 / expected as f64
+    }
+}
+
+// The final report of a run against several providers.
+#[derive(Debug, Serialize)]
+pub struct ProvidersReport {
+    pub providers: Vec<ProviderReport>,
+}
+
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    endpoint: &'a str,
+    total_blocks: u64,
+    verified_blocks: u64,
+    mismatched_blocks: u64,
+    missing_blocks: u64,
+    unverified_slots: u64,
+    completeness: f64,
+    first: u64,
+    absent: u64,
+    late: u64,
+    replaced: u64,
+    disagreements: u64,
+    lag_avg_ms: u64,
+    lag_p50_ms: Option<i64>,
+    lag_p90_ms: Option<i64>,
+    lag_p99_ms: Option<i64>,
+    lag_max_ms: u64,
+}
+
+impl RenderReport for ProvidersReport {
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        for provider in &self.providers {
+            writeln!(out, "\n##### {} #####", provider.endpoint)?;
+            provider.report.write_text(out)?;
+        }
+
+        writeln!(out, "\n============ PROVIDER COMPARISON ============")?;
+        writeln!(
+            out,
+            "{:<4}{:>9}{:>10}{:>12}{:>9}{:>14}{:>7}{:>8}{:>10}{:>10}{:>10}",
+            "#",
+            "Blocks",
+            "Verified",
+            "Mismatched",
+            "Missing",
+            "Completeness",
+            "First",
+            "Absent",
+            "Disagree",
+            "Avg Lag",
+            "Max Lag"
+        )?;
+        for (index, provider) in self.providers.iter().enumerate() {
+            let report = &provider.report;
+            let stats = &provider.comparison;
+            writeln!(
+                out,
+                "{:<4}{:>9}{:>10}{:>12}{:>9}{:>13.2}%{:>7}{:>8}{:>10}{:>8}ms{:>8}ms",
+                index + 1,
+                report.total_blocks,
+                report.verified_blocks,
+                report.mismatched_blocks,
+                report.missing_blocks,
+                provider.completeness(),
+                stats.first,
+                stats.absent,
+                stats.disagreements,
+                stats.lag_avg().as_millis(),
+                stats.lag_max.as_millis()
+            )?;
+        }
+        for (index, provider) in self.providers.iter().enumerate() {
+            writeln!(out, "#{} {}", index + 1, provider.endpoint)?;
+        }
+
+        writeln!(out, "\nLag behind the fastest provider:")?;
+        for (index, provider) in self.providers.iter().enumerate() {
+            if let Some(summary) = provider.comparison.lag.summary() {
+                writeln!(out, "#{} {}", index + 1, summary)?;
+            }
+        }
+        writeln!(out, "=============================================")
+    }
+
+    fn write_csv(&self, out: &mut dyn Write) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_writer(out);
+        for provider in &self.providers {
+            let report = &provider.report;
+            let stats = &provider.comparison;
+            let lag = stats.lag.summary();
+            writer.serialize(CsvRow {
+                endpoint: &provider.endpoint,
+                total_blocks: report.total_blocks,
+                verified_blocks: report.verified_blocks,
+                mismatched_blocks: report.mismatched_blocks,
+                missing_blocks: report.missing_blocks,
+                unverified_slots: report.unverified_slots,
+                completeness: provider.completeness(),
+                first: stats.first,
+                absent: stats.absent,
+                late: stats.late,
+                replaced: stats.replaced,
+                disagreements: stats.disagreements,
+                lag_avg_ms: stats.lag_avg().as_millis() as u64,
+                lag_p50_ms: lag.map(|lag| lag.p50_ms),
+                lag_p90_ms: lag.map(|lag| lag.p90_ms),
+                lag_p99_ms: lag.map(|lag| lag.p99_ms),
+                lag_max_ms: stats.lag_max.as_millis() as u64,
+            })?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use {
+    crate::{
+        rpc::{ErrorClass, UnverifiedReason, block_signatures, classify_error, slot_skipped_error},
+        source::ReferenceBlockSource,
+    },
+    futures::future::join_all,
+    serde::Serialize,
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        nonblocking::rpc_client::RpcClient,
+        rpc_config::{CommitmentConfig, RpcBlockConfig},
+        rpc_response::UiConfirmedBlock,
+    },
+    std::{
+        collections::{BTreeMap, HashMap},
+        sync::Mutex,
+    },
+};
+
+// What an RPC endpoint answered for a slot.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Answer {
+    Block {
+        blockhash: String,
+        signatures: Vec<String>,
+    },
+    Skipped,
+}
+
+impl Answer {
+    fn of(block: &UiConfirmedBlock) -> Self {
+        Self::Block {
+            blockhash: block.blockhash.clone(),
+            signatures: block_signatures(block),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Block {
+                blockhash,
+                signatures,
+            } => format!("blockhash {} with {} txs", blockhash, signatures.len()),
+            Self::Skipped => "skipped".to_string(),
+        }
+    }
+}
+
+// RPC endpoints of a quorum that did not all answer a slot the same way.
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcDisagreement {
+    // whether a majority still agreed, in which case its block was used
+    pub quorum: bool,
+    // (endpoint, answer) for every endpoint, errors included
+    pub answers: Vec<(String, String)>,
+}
+
+// Several RPC endpoints taken as one reference: a block is the truth once a
+// majority of them return the same blockhash and signatures.
+pub struct RpcQuorum {
+    endpoints: Vec<(String, RpcClient)>,
+    // disagreements of the last getBlock of a slot, until the verifier takes them
+    disagreements: Mutex<HashMap<u64, RpcDisagreement>>,
+}
+
+impl RpcQuorum {
+    pub fn new(rpc_uris: &[String]) -> Self {
+        Self {
+            endpoints: rpc_uris
+                .iter()
+                .map(|uri| {
+                    let client =
+                        RpcClient::new_with_commitment(uri.clone(), CommitmentConfig::finalized());
+                    (uri.clone(), client)
+                })
+                .collect(),
+            disagreements: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // endpoints that have to agree
+    pub fn quorum(&self) -> usize {
+        self.endpoints.len() / 2 + 1
+    }
+
+    fn no_quorum_error(&self, what: String) -> ClientError {
+        ClientErrorKind::Custom(format!(
+            "fewer than {} of {} RPC endpoints agree on {}",
+            self.quorum(),
+            self.endpoints.len(),
+            what
+        ))
+        .into()
+    }
+}
+
+impl ReferenceBlockSource for RpcQuorum {
+    async fn get_block(
+        &self,
+        slot: u64,
+        config: RpcBlockConfig,
+    ) -> Result<UiConfirmedBlock, ClientError> {
+        let responses = join_all(
+            self.endpoints
+                .iter()
+                .map(|(_, client)| ReferenceBlockSource::get_block(client, slot, config)),
+        )
+        .await;
+
+        let mut blocks = HashMap::new();
+        let mut votes = vec![];
+        for response in responses {
+            votes.push(match response {
+                Ok(block) => {
+                    let answer = Answer::of(&block);
+                    blocks.entry(answer.clone()).or_insert(block);
+                    Ok(answer)
+                }
+                Err(e) => match classify_error(&e) {
+                    ErrorClass::Terminal(UnverifiedReason::SlotSkipped) => Ok(Answer::Skipped),
+                    _ => Err(e),
+                },
+            });
+        }
+
+        let mut counts: HashMap<&Answer, usize> = HashMap::new();
+        for answer in votes.iter().flatten() {
+            *counts.entry(answer).or_default() += 1;
+        }
+        let majority = counts
+            .iter()
+            .find(|(_, count)| **count >= self.quorum())
+            .map(|(answer, _)| (*answer).clone());
+        let disagree = counts.len() > 1;
+
+        let mut disagreements = self.disagreements.lock().unwrap();
+        if disagree {
+            let answers = self
+                .endpoints
+                .iter()
+                .zip(&votes)
+                .map(|((endpoint, _), vote)| {
+                    let answer = match vote {
+                        Ok(answer) => answer.describe(),
+                        Err(e) => format!("error: {}", e),
+                    };
+                    (endpoint.clone(), answer)
+                })
+                .collect();
+            disagreements.insert(
+                slot,
+                RpcDisagreement {
+                    quorum: majority.is_some(),
+                    answers,
+                },
+            );
+        } else {
+            disagreements.remove(&slot);
+        }
+        drop(disagreements);
+
+        match majority {
+            Some(Answer::Skipped) => Err(slot_skipped_error(slot)),
+            Some(answer) => Ok(blocks.remove(&answer).expect("majority answered a block")),
+            None => {
+                let mut errors: Vec<ClientError> =
+                    votes.into_iter().filter_map(Result::err).collect();
+                // endpoints lagging behind may still catch up, so transient errors are retried
+                if let Some(index) = errors
+                    .iter()
+                    .position(|e| matches!(classify_error(e), ErrorClass::Transient))
+                {
+                    return Err(errors.swap_remove(index));
+                }
+                match errors.into_iter().next() {
+                    Some(e) if !disagree => Err(e),
+                    _ => Err(self.no_quorum_error(format!("slot {}", slot))),
+                }
+            }
+        }
+    }
+
+    // Slots a majority of endpoints confirmed.
+    async fn get_blocks(&self, start: u64, end: u64) -> Result<Vec<u64>, ClientError> {
+        let responses = join_all(
+            self.endpoints
+                .iter()
+                .map(|(_, client)| ReferenceBlockSource::get_blocks(client, start, end)),
+        )
+        .await;
+
+        let mut counts: BTreeMap<u64, usize> = BTreeMap::new();
+        let mut answered = 0;
+        let mut last_error = None;
+        for response in responses {
+            match response {
+                Ok(slots) => {
+                    answered += 1;
+                    for slot in slots {
+                        *counts.entry(slot).or_default() += 1;
+                    }
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        if answered < self.quorum() {
+            return Err(last_error
+                .unwrap_or_else(|| self.no_quorum_error(format!("blocks {}-{}", start, end))));
+        }
+        Ok(counts
+            .into_iter()
+            .filter(|(_, count)| *count >= self.quorum())
+            .map(|(slot, _)| slot)
+            .collect())
+    }
+
+    // The highest slot a majority of endpoints has finalized.
+    async fn get_slot(&self) -> Result<u64, ClientError> {
+        let responses = join_all(
+            self.endpoints
+                .iter()
+                .map(|(_, client)| ReferenceBlockSource::get_slot(client)),
+        )
+        .await;
+
+        let mut slots = vec![];
+        let mut last_error = None;
+        for response in responses {
+            match response {
+                Ok(slot) => slots.push(slot),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        slots.sort_unstable_by(|a, b| b.cmp(a));
+        match slots.get(self.quorum() - 1) {
+            Some(slot) => Ok(*slot),
+            None => {
+                Err(last_error
+                    .unwrap_or_else(|| self.no_quorum_error("the latest slot".to_string())))
+            }
+        }
+    }
+
+    fn take_disagreement(&self, slot: u64) -> Option<RpcDisagreement> {
+        self.disagreements.lock().unwrap().remove(&slot)
+    }
+}
@@ -0,0 +1,91 @@
+use {
+    std::{
+        fs::File,
+        io::{self, BufRead, BufReader, BufWriter, Read, Write},
+        path::Path,
+    },
+    yellowstone_grpc_proto::{geyser::SubscribeUpdate, prost::Message},
+};
+
+// first bytes of every zstd frame
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// Writes raw `SubscribeUpdate`s as length-delimited protobuf, zstd-compressed
+// when the file name ends in `.zst`.
+pub struct Recorder {
+    out: Box<dyn Write + Send>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let out: Box<dyn Write + Send> = if path.extension().is_some_and(|ext| ext == "zst") {
+            // the zstd frame is completed when the recorder is dropped
+            Box::new(zstd::Encoder::new(file, 0)?.auto_finish())
+        } else {
+            Box::new(file)
+        };
+        Ok(Self { out })
+    }
+
+    pub fn write(&mut self, update: &SubscribeUpdate) -> io::Result<()> {
+        self.out.write_all(&update.encode_length_delimited_to_vec())
+    }
+}
+
+// Reads back the updates written by a `Recorder`, compressed or not.
+pub struct Recording {
+    input: Box<dyn Read + Send>,
+}
+
+impl Recording {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let input: Box<dyn Read + Send> = if file.fill_buf()?.starts_with(&ZSTD_MAGIC) {
+            Box::new(zstd::Decoder::with_buffer(file)?)
+        } else {
+            Box::new(file)
+        };
+        Ok(Self { input })
+    }
+
+    fn read_update(&mut self) -> anyhow::Result<Option<SubscribeUpdate>> {
+        let Some(length) = self.read_length()? else {
+            return Ok(None);
+        };
+        let mut buf = vec![0; length];
+        self.input.read_exact(&mut buf)?;
+        Ok(Some(SubscribeUpdate::decode(buf.as_slice())?))
+    }
+
+    // Reads the varint length prefix, or None at a clean end of file.
+    fn read_length(&mut self) -> io::Result<Option<usize>> {
+        let mut length = 0u64;
+        for shift in (0..64).step_by(7) {
+            let mut byte = [0u8];
+            match self.input.read_exact(&mut byte) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && shift == 0 => {
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            }
+            length |= u64::from(byte[0] & 0x7f) << shift;
+            if byte[0] & 0x80 == 0 {
+                return Ok(Some(length as usize));
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid length delimiter",
+        ))
+    }
+}
+
+impl Iterator for Recording {
+    type Item = anyhow::Result<SubscribeUpdate>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_update().transpose()
+    }
+}
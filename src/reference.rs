@@ -0,0 +1,326 @@
+use {
+    crate::{
+        gaps::MAX_GET_BLOCKS_RANGE,
+        geyser_reference::GeyserReference,
+        quorum::{RpcDisagreement, RpcQuorum},
+        report::Report,
+        rpc::{block_config, get_block_with_retry, slot_skipped_error},
+        source::ReferenceBlockSource,
+    },
+    futures::{StreamExt, stream},
+    log::{error, info},
+    serde::{Deserialize, Serialize},
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        nonblocking::rpc_client::RpcClient,
+        rpc_config::{CommitmentConfig, RpcBlockConfig, TransactionDetails},
+        rpc_response::UiConfirmedBlock,
+    },
+    std::{
+        collections::BTreeMap,
+        fs, io,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    tokio::sync::OnceCell,
+};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+// slots a shared block is kept behind the highest one fetched, for the
+// providers that never deliver it
+const SHARED_SLOTS: u64 = 150;
+
+// The "truth" side of the comparison: a live RPC endpoint, a quorum of them,
+// a directory of `getBlock` responses captured with `snapshot-rpc`, or a
+// trusted Geyser endpoint.
+pub enum ReferenceSource {
+    Rpc(RpcClient),
+    Quorum(RpcQuorum),
+    Fixtures(Fixtures),
+    Geyser(GeyserReference),
+}
+
+impl ReferenceSource {
+    pub fn new(rpc_uris: &[String], fixtures: Option<&Path>) -> anyhow::Result<Self> {
+        Ok(match (fixtures, rpc_uris) {
+            (Some(dir), _) => Self::Fixtures(Fixtures::open(dir)?),
+            (None, [rpc_uri]) => Self::Rpc(RpcClient::new_with_commitment(
+                rpc_uri.clone(),
+                CommitmentConfig::finalized(),
+            )),
+            (None, []) => anyhow::bail!("at least one --rpc-uri is required"),
+            (None, rpc_uris) => Self::Quorum(RpcQuorum::new(rpc_uris)),
+        })
+    }
+}
+
+impl ReferenceBlockSource for ReferenceSource {
+    async fn get_block(
+        &self,
+        slot: u64,
+        config: RpcBlockConfig,
+    ) -> Result<UiConfirmedBlock, ClientError> {
+        match self {
+            Self::Rpc(client) => client.get_block_with_config(slot, config).await,
+            Self::Quorum(quorum) => quorum.get_block(slot, config).await,
+            Self::Geyser(geyser) => geyser.get_block(slot, config).await,
+            Self::Fixtures(fixtures) => fixtures.get_block(slot, config).await,
+        }
+    }
+
+    async fn get_blocks(&self, start: u64, end: u64) -> Result<Vec<u64>, ClientError> {
+        match self {
+            Self::Rpc(client) => ReferenceBlockSource::get_blocks(client, start, end).await,
+            Self::Quorum(quorum) => quorum.get_blocks(start, end).await,
+            Self::Geyser(geyser) => geyser.get_blocks(start, end).await,
+            Self::Fixtures(fixtures) => fixtures.get_blocks(start, end).await,
+        }
+    }
+
+    async fn get_slot(&self) -> Result<u64, ClientError> {
+        match self {
+            Self::Rpc(client) => ReferenceBlockSource::get_slot(client).await,
+            Self::Quorum(quorum) => quorum.get_slot().await,
+            Self::Geyser(geyser) => geyser.get_slot().await,
+            Self::Fixtures(fixtures) => fixtures.get_slot().await,
+        }
+    }
+
+    fn take_disagreement(&self, slot: u64) -> Option<RpcDisagreement> {
+        match self {
+            Self::Quorum(quorum) => quorum.take_disagreement(slot),
+            Self::Rpc(_) | Self::Fixtures(_) | Self::Geyser(_) => None,
+        }
+    }
+}
+
+// One reference shared by the verifiers of several providers, so a slot is
+// fetched once however many of them deliver it. A block is kept until every
+// provider has verified it or it falls SHARED_SLOTS behind the highest one;
+// failed fetches are not kept, the next caller retries them.
+pub struct SharedReference<R> {
+    inner: R,
+    readers: usize,
+    blocks: Mutex<BTreeMap<u64, SharedBlock>>,
+}
+
+struct SharedBlock {
+    details: Option<TransactionDetails>,
+    block: Arc<OnceCell<UiConfirmedBlock>>,
+    disagreement: Option<RpcDisagreement>,
+    reads: usize,
+}
+
+impl<R: ReferenceBlockSource> SharedReference<R> {
+    // `readers` is the number of verifiers sharing the reference.
+    pub fn new(inner: R, readers: usize) -> Self {
+        Self {
+            inner,
+            readers,
+            blocks: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    async fn fetch(
+        &self,
+        slot: u64,
+        config: RpcBlockConfig,
+    ) -> Result<UiConfirmedBlock, ClientError> {
+        let fetched = self.inner.get_block(slot, config).await;
+        let disagreement = self.inner.take_disagreement(slot);
+        if let Some(shared) = self.blocks.lock().unwrap().get_mut(&slot) {
+            shared.disagreement = disagreement;
+        }
+        fetched
+    }
+}
+
+impl<R: ReferenceBlockSource> ReferenceBlockSource for SharedReference<R> {
+    async fn get_block(
+        &self,
+        slot: u64,
+        config: RpcBlockConfig,
+    ) -> Result<UiConfirmedBlock, ClientError> {
+        let cached = {
+            let mut blocks = self.blocks.lock().unwrap();
+            if blocks
+                .last_key_value()
+                .is_none_or(|(highest, _)| slot > *highest)
+            {
+                let retained = blocks.split_off(&slot.saturating_sub(SHARED_SLOTS));
+                *blocks = retained;
+            }
+            let shared = blocks.entry(slot).or_insert_with(|| SharedBlock {
+                details: config.transaction_details,
+                block: Arc::default(),
+                disagreement: None,
+                reads: 0,
+            });
+            (shared.details == config.transaction_details).then(|| shared.block.clone())
+        };
+        match cached {
+            Some(block) => block
+                .get_or_try_init(|| self.fetch(slot, config))
+                .await
+                .cloned(),
+            // fetched with other transaction details than the kept block
+            None => self.fetch(slot, config).await,
+        }
+    }
+
+    async fn get_blocks(&self, start: u64, end: u64) -> Result<Vec<u64>, ClientError> {
+        self.inner.get_blocks(start, end).await
+    }
+
+    async fn get_slot(&self) -> Result<u64, ClientError> {
+        self.inner.get_slot().await
+    }
+
+    // Every verifier takes the disagreement once it is done with a slot, the
+    // last one lets the block go.
+    fn take_disagreement(&self, slot: u64) -> Option<RpcDisagreement> {
+        let mut blocks = self.blocks.lock().unwrap();
+        let shared = blocks.get_mut(&slot)?;
+        shared.reads += 1;
+        if shared.reads < self.readers {
+            return shared.disagreement.clone();
+        }
+        blocks.remove(&slot).and_then(|shared| shared.disagreement)
+    }
+}
+
+// The slot range a fixture directory covers. Slots in the range without a
+// `<slot>.json` file were skipped.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FixtureManifest {
+    pub from_slot: u64,
+    pub to_slot: u64,
+}
+
+pub struct Fixtures {
+    dir: PathBuf,
+    manifest: FixtureManifest,
+}
+
+impl Fixtures {
+    pub fn open(dir: &Path) -> anyhow::Result<Self> {
+        let manifest = fs::read(dir.join(MANIFEST_FILE))
+            .map_err(|e| anyhow::anyhow!("no fixtures in {}: {}", dir.display(), e))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            manifest: serde_json::from_slice(&manifest)?,
+        })
+    }
+
+    pub fn block_path(dir: &Path, slot: u64) -> PathBuf {
+        dir.join(format!("{}.json", slot))
+    }
+}
+
+// Fixtures are captured with full transaction details, which also serve
+// signature-only requests.
+impl ReferenceBlockSource for Fixtures {
+    async fn get_block(
+        &self,
+        slot: u64,
+        _config: RpcBlockConfig,
+    ) -> Result<UiConfirmedBlock, ClientError> {
+        let FixtureManifest { from_slot, to_slot } = self.manifest;
+        if !(from_slot..=to_slot).contains(&slot) {
+            return Err(ClientErrorKind::Custom(format!(
+                "slot {} is outside the fixture range {}-{}",
+                slot, from_slot, to_slot
+            ))
+            .into());
+        }
+
+        match fs::read(Self::block_path(&self.dir, slot)) {
+            Ok(block) => {
+                serde_json::from_slice(&block).map_err(|e| ClientErrorKind::SerdeJson(e).into())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(slot_skipped_error(slot)),
+            // an io error would be retried as transient, but a local file will not recover
+            Err(e) => Err(ClientErrorKind::Custom(e.to_string()).into()),
+        }
+    }
+
+    async fn get_blocks(&self, start: u64, end: u64) -> Result<Vec<u64>, ClientError> {
+        let start = start.max(self.manifest.from_slot);
+        let end = end.min(self.manifest.to_slot);
+        Ok((start..=end)
+            .filter(|slot| Self::block_path(&self.dir, *slot).exists())
+            .collect())
+    }
+
+    async fn get_slot(&self) -> Result<u64, ClientError> {
+        Ok(self.manifest.to_slot)
+    }
+}
+
+// Captures full `getBlock` responses for every finalized block in the range,
+// fetching up to `concurrency` blocks at once. The manifest is written last, so
+// an interrupted capture is not mistaken for a complete one.
+pub async fn snapshot_rpc(
+    client: &impl ReferenceBlockSource,
+    retry_timeout: Duration,
+    concurrency: usize,
+    from_slot: u64,
+    to_slot: u64,
+    out: &Path,
+) -> anyhow::Result<()> {
+    fs::create_dir_all(out)?;
+
+    let mut slots = vec![];
+    let mut chunk_start = from_slot;
+    while chunk_start <= to_slot {
+        let chunk_end = to_slot.min(chunk_start + MAX_GET_BLOCKS_RANGE - 1);
+        slots.extend(client.get_blocks(chunk_start, chunk_end).await?);
+        chunk_start = chunk_end + 1;
+    }
+    info!(
+        "Capturing {} blocks for slots {}-{} into {}",
+        slots.len(),
+        from_slot,
+        to_slot,
+        out.display()
+    );
+
+    // retries are only logged here, there is no report to count them in
+    let report = Arc::new(Mutex::new(Report::default()));
+    let mut fetches = stream::iter(slots)
+        .map(|slot| {
+            let report = &report;
+            async move {
+                let fetched =
+                    get_block_with_retry(client, slot, block_config(true), retry_timeout, report)
+                        .await;
+                // a quorum keeps disagreements until they are taken, the
+                // fixtures do not record them
+                client.take_disagreement(slot);
+                (slot, fetched)
+            }
+        })
+        .buffer_unordered(concurrency);
+
+    let mut failed = 0;
+    while let Some((slot, fetched)) = fetches.next().await {
+        match fetched {
+            Ok(block) => fs::write(Fixtures::block_path(out, slot), serde_json::to_vec(&block)?)?,
+            Err((reason, e)) => {
+                error!("Failed to capture slot {} → {}: {}", slot, reason, e);
+                failed += 1;
+            }
+        }
+    }
+    anyhow::ensure!(failed == 0, "{} blocks could not be captured", failed);
+
+    let manifest = FixtureManifest { from_slot, to_slot };
+    fs::write(
+        out.join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    info!("Captured fixtures for slots {}-{}", from_slot, to_slot);
+    Ok(())
+}
@@ -0,0 +1,535 @@
+use {
+    crate::{
+        latency::Latency,
+        meta::MetaDiff,
+        metrics,
+        pipeline::BackpressureStats,
+        quorum::RpcDisagreement,
+        rpc::UnverifiedReason,
+        slots::{SlotAnomalyKind, SlotStatusStats},
+    },
+    clap::ValueEnum,
+    serde::{Serialize, Serializer},
+    std::{
+        collections::{HashMap, HashSet},
+        fs::File,
+        io::{self, BufWriter, Write},
+        path::Path,
+        time::Duration,
+    },
+};
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+// A report that can be rendered in every `OutputFormat`.
+pub trait RenderReport: Serialize {
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()>;
+    fn write_csv(&self, out: &mut dyn Write) -> anyhow::Result<()>;
+}
+
+// Writes the report to `path`, or to stdout when no path is given.
+pub fn emit_report(
+    report: &impl RenderReport,
+    format: OutputFormat,
+    path: Option<&Path>,
+) -> anyhow::Result<()> {
+    let mut out: Box<dyn Write> = match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    match format {
+        OutputFormat::Text => report.write_text(&mut out)?,
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, report)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv => report.write_csv(&mut out)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+pub fn duration_ms<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockStatus {
+    Match,
+    Mismatch,
+    // received from gRPC but RPC could not serve it
+    Unverified,
+    // confirmed by RPC but never received from gRPC
+    Missing,
+    // received below finalized commitment, but another block or none was
+    // finalized for the slot
+    RolledBack,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SlotRecord {
+    pub slot: u64,
+    pub status: BlockStatus,
+    pub grpc_tx_count: Option<u64>,
+    pub rpc_tx_count: Option<u64>,
+    // time spent fetching the block from RPC, retries included
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unverified_reason: Option<UnverifiedReason>,
+    #[serde(skip_serializing_if = "SignatureDiff::is_empty")]
+    pub signature_diff: SignatureDiff,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub meta_diffs: Vec<MetaDiff>,
+    // set when the RPC endpoints of a quorum answered the slot differently
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_disagreement: Option<RpcDisagreement>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub received_at_unix_ms: Option<i64>,
+    // arrival minus the block's block_time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time_lag_ms: Option<i64>,
+    // arrival until RPC first served the block, less the finalization delay
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_lag_ms: Option<i64>,
+}
+
+impl SlotRecord {
+    pub fn new(slot: u64, status: BlockStatus) -> Self {
+        Self {
+            slot,
+            status,
+            grpc_tx_count: None,
+            rpc_tx_count: None,
+            latency_ms: None,
+            error: None,
+            unverified_reason: None,
+            signature_diff: SignatureDiff::default(),
+            meta_diffs: vec![],
+            rpc_disagreement: None,
+            received_at_unix_ms: None,
+            block_time_lag_ms: None,
+            rpc_lag_ms: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    // the `provider` label of the stream's metrics
+    #[serde(skip)]
+    pub provider: String,
+    // (from, to) slots of a backfill run
+    pub backfill_range: Option<(u64, u64)>,
+    pub total_blocks: u64,
+    pub verified_blocks: u64,
+    pub unverified_slots: u64,
+    pub mismatched_blocks: u64,
+    pub missing_blocks: u64,
+    pub total_grpc_txs: u64,
+    pub total_rpc_txs: u64,
+    pub reconnects: u64,
+    // reconnects forced by the stall watchdog
+    pub stalls: u64,
+    // (first, last) finalized slots produced while the stream was disconnected
+    pub replay_windows: Vec<(u64, u64)>,
+    pub replayed_blocks: u64,
+    pub lost_blocks: u64,
+    pub duplicate_blocks: u64,
+    // blocks received below finalized commitment that were never finalized
+    pub rolled_back_blocks: u64,
+    // slots received again with a different blockhash after a fork switch
+    pub duplicate_slots: u64,
+    pub replay_rejections: u64,
+    pub rpc_retries: u64,
+    // slots the RPC endpoints of a quorum disagreed on, whatever gRPC delivered
+    pub rpc_disagreements: u64,
+    // received, but still queued or being verified when the shutdown timeout hit
+    pub abandoned_blocks: u64,
+    pub backpressure: BackpressureStats,
+    pub latency: Latency,
+    pub slot_status: SlotStatusStats,
+    pub slots: Vec<SlotRecord>,
+}
+
+impl Report {
+    pub fn in_replay_window(&self, slot: u64) -> bool {
+        self.replay_windows
+            .iter()
+            .any(|(first, last)| (*first..=*last).contains(&slot))
+    }
+
+    pub fn record_verified(&mut self, record: SlotRecord) {
+        self.tally(&record);
+        self.verified_blocks += 1;
+        metrics::BLOCKS_VERIFIED
+            .with_label_values(&[&self.provider])
+            .inc();
+        if record.status == BlockStatus::Mismatch {
+            self.mismatched_blocks += 1;
+            metrics::MISMATCHED_BLOCKS
+                .with_label_values(&[&self.provider])
+                .inc();
+        }
+        self.total_rpc_txs += record.rpc_tx_count.unwrap_or_default();
+        self.slots.push(record);
+    }
+
+    pub fn record_unverified(&mut self, record: SlotRecord) {
+        self.tally(&record);
+        self.unverified_slots += 1;
+        metrics::UNVERIFIED_SLOTS
+            .with_label_values(&[&self.provider])
+            .inc();
+        self.slots.push(record);
+    }
+
+    pub fn record_rolled_back(&mut self, record: SlotRecord) {
+        self.tally(&record);
+        self.rolled_back_blocks += 1;
+        metrics::ROLLED_BACK_BLOCKS
+            .with_label_values(&[&self.provider])
+            .inc();
+        self.slots.push(record);
+    }
+
+    pub fn record_missing(&mut self, slot: u64) {
+        self.missing_blocks += 1;
+        metrics::MISSING_BLOCKS
+            .with_label_values(&[&self.provider])
+            .inc();
+        if self.in_replay_window(slot) {
+            self.lost_blocks += 1;
+        }
+        self.slots.push(SlotRecord::new(slot, BlockStatus::Missing));
+    }
+
+    // Counts what a received slot carries besides its status.
+    fn tally(&mut self, record: &SlotRecord) {
+        if record.rpc_disagreement.is_some() {
+            self.rpc_disagreements += 1;
+            metrics::RPC_DISAGREEMENTS
+                .with_label_values(&[&self.provider])
+                .inc();
+        }
+        if let Some(lag) = record.block_time_lag_ms {
+            self.latency.block_time.record(lag);
+            metrics::BLOCK_TIME_LAG
+                .with_label_values(&[&self.provider])
+                .observe(lag as f64 / 1000.0);
+        }
+        if let Some(lag) = record.rpc_lag_ms {
+            self.latency.rpc.record(lag);
+            metrics::GRPC_TO_RPC_LAG
+                .with_label_values(&[&self.provider])
+                .observe(lag as f64 / 1000.0);
+        }
+    }
+
+    fn with_status(&self, status: BlockStatus) -> impl Iterator<Item = &SlotRecord> {
+        self.slots
+            .iter()
+            .filter(move |record| record.status == status)
+    }
+}
+
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    slot: u64,
+    status: BlockStatus,
+    grpc_tx_count: Option<u64>,
+    rpc_tx_count: Option<u64>,
+    latency_ms: Option<u64>,
+    error: Option<&'a str>,
+    missing_signatures: usize,
+    extra_signatures: usize,
+    duplicated_signatures: usize,
+    meta_diffs: usize,
+    rpc_disagreement: bool,
+    received_at_unix_ms: Option<i64>,
+    block_time_lag_ms: Option<i64>,
+    rpc_lag_ms: Option<i64>,
+}
+
+impl RenderReport for Report {
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\n============== FINAL REPORT ==============")?;
+        if let Some((from, to)) = self.backfill_range {
+            writeln!(out, "Backfill Range: {}-{}", from, to)?;
+        }
+        writeln!(out, "Total Blocks Received: {}", self.total_blocks)?;
+        writeln!(out, "Total gRPC Tx Count: {}", self.total_grpc_txs)?;
+        writeln!(out, "Total RPC Tx Count: {}", self.total_rpc_txs)?;
+        writeln!(out, "Verified Blocks: {}", self.verified_blocks)?;
+        writeln!(out, "Unverified Slots: {}", self.unverified_slots)?;
+        writeln!(out, "Mismatched Blocks: {}", self.mismatched_blocks)?;
+        writeln!(out, "Missing Blocks: {}", self.missing_blocks)?;
+        if self.rpc_disagreements > 0 {
+            writeln!(out, "RPC Disagreements: {}", self.rpc_disagreements)?;
+        }
+        if self.abandoned_blocks > 0 {
+            writeln!(out, "Abandoned At Shutdown: {}", self.abandoned_blocks)?;
+        }
+        if self.rolled_back_blocks > 0 || self.duplicate_slots > 0 {
+            writeln!(out, "Rolled Back Blocks: {}", self.rolled_back_blocks)?;
+            writeln!(out, "Duplicate Slot Events: {}", self.duplicate_slots)?;
+        }
+        writeln!(out, "Reconnects: {}", self.reconnects)?;
+        if self.stalls > 0 {
+            writeln!(out, "Stalls Detected: {}", self.stalls)?;
+        }
+        if self.reconnects > 0 {
+            writeln!(out, "Blocks Recovered By Replay: {}", self.replayed_blocks)?;
+            writeln!(out, "Blocks Lost During Disconnects: {}", self.lost_blocks)?;
+            writeln!(out, "Duplicate Blocks Skipped: {}", self.duplicate_blocks)?;
+            writeln!(out, "Replay Rejections: {}", self.replay_rejections)?;
+        }
+
+        let bp = &self.backpressure;
+        writeln!(out, "\n--- VERIFICATION PIPELINE ---")?;
+        writeln!(out, "RPC Workers: {}", bp.workers)?;
+        writeln!(
+            out,
+            "Max Queue Depth: {}/{}",
+            bp.max_queue_depth, bp.queue_capacity
+        )?;
+        writeln!(out, "Queue Full Events: {}", bp.queue_full_events)?;
+        writeln!(
+            out,
+            "Stream Blocked On Full Queue: total={:?} max={:?}",
+            bp.enqueue_wait_total, bp.enqueue_wait_max
+        )?;
+        writeln!(out, "Max Queue Wait: {:?}", bp.queue_wait_max)?;
+        writeln!(out, "RPC Retries: {}", self.rpc_retries)?;
+
+        let block_time = self.latency.block_time.summary();
+        let rpc = self.latency.rpc.summary();
+        if block_time.is_some() || rpc.is_some() {
+            writeln!(out, "\n--- LATENCY ---")?;
+            if let Some(summary) = block_time {
+                writeln!(out, "Arrival After Block Time: {}", summary)?;
+            }
+            if let Some(summary) = rpc {
+                writeln!(out, "RPC Served After Arrival: {}", summary)?;
+            }
+        }
+
+        let slot_status = &self.slot_status;
+        if slot_status.updates > 0 {
+            writeln!(out, "\n--- SLOT STATUS ---")?;
+            writeln!(out, "Slot Updates: {}", slot_status.updates)?;
+            writeln!(out, "Finalized Slots: {}", slot_status.finalized_slots)?;
+            writeln!(out, "Dead Slots: {}", slot_status.dead_slots)?;
+            writeln!(
+                out,
+                "Skipped Statuses: {}",
+                slot_status.count(SlotAnomalyKind::SkippedStatus)
+            )?;
+            writeln!(
+                out,
+                "Status Regressions: {}",
+                slot_status.count(SlotAnomalyKind::Regression)
+            )?;
+            writeln!(
+                out,
+                "Finalized But Not Delivered: {}",
+                slot_status.count(SlotAnomalyKind::Undelivered)
+            )?;
+            for anomaly in &slot_status.anomalies {
+                writeln!(
+                    out,
+                    "Slot {} {}: {}",
+                    anomaly.slot, anomaly.kind, anomaly.detail
+                )?;
+            }
+        }
+
+        if self.mismatched_blocks > 0 {
+            writeln!(out, "\n--- MISMATCH DETAILS ---")?;
+            for record in self.with_status(BlockStatus::Mismatch) {
+                writeln!(
+                    out,
+                    "Slot {} mismatch: gRPC Tx Count={} RPC Tx Count={} Missing={} Extra={} Duplicated={} Meta Diffs={}",
+                    record.slot,
+                    record.grpc_tx_count.unwrap_or_default(),
+                    record.rpc_tx_count.unwrap_or_default(),
+                    record.signature_diff.missing.len(),
+                    record.signature_diff.extra.len(),
+                    record.signature_diff.duplicated.len(),
+                    record.meta_diffs.len()
+                )?;
+            }
+        }
+
+        if self.unverified_slots > 0 {
+            writeln!(out, "\n--- UNVERIFIED SLOTS ---")?;
+            for record in self.with_status(BlockStatus::Unverified) {
+                writeln!(
+                    out,
+                    "Slot {} unverified ({}): {}",
+                    record.slot,
+                    record
+                        .unverified_reason
+                        .map_or_else(|| "unknown".to_string(), |reason| reason.to_string()),
+                    record.error.as_deref().unwrap_or_default()
+                )?;
+            }
+        }
+
+        if self.rolled_back_blocks > 0 {
+            writeln!(out, "\n--- ROLLED BACK BLOCKS ---")?;
+            for record in self.with_status(BlockStatus::RolledBack) {
+                writeln!(
+                    out,
+                    "Slot {} rolled back: {}",
+                    record.slot,
+                    record.error.as_deref().unwrap_or_default()
+                )?;
+            }
+        }
+
+        if self.missing_blocks > 0 {
+            writeln!(out, "\n--- MISSING BLOCKS ---")?;
+            for record in self.with_status(BlockStatus::Missing) {
+                writeln!(
+                    out,
+                    "Slot {} confirmed by RPC but never delivered by gRPC",
+                    record.slot
+                )?;
+            }
+        }
+
+        let mut header_written = false;
+        for record in self.slots.iter().filter(|r| !r.signature_diff.is_empty()) {
+            if !header_written {
+                writeln!(out, "\n--- SIGNATURE DIFFS ---")?;
+                header_written = true;
+            }
+            let diff = &record.signature_diff;
+            writeln!(out, "Slot {}:", record.slot)?;
+            for sig in &diff.missing {
+                writeln!(out, "  missing    {}", sig)?;
+            }
+            for sig in &diff.extra {
+                writeln!(out, "  extra      {}", sig)?;
+            }
+            for (sig, count) in &diff.duplicated {
+                writeln!(out, "  duplicated {} (x{})", sig, count)?;
+            }
+        }
+
+        let mut header_written = false;
+        for record in &self.slots {
+            let Some(disagreement) = &record.rpc_disagreement else {
+                continue;
+            };
+            if !header_written {
+                writeln!(out, "\n--- RPC DISAGREEMENTS ---")?;
+                header_written = true;
+            }
+            writeln!(
+                out,
+                "Slot {} ({}):",
+                record.slot,
+                if disagreement.quorum {
+                    "majority used"
+                } else {
+                    "no quorum"
+                }
+            )?;
+            for (endpoint, answer) in &disagreement.answers {
+                writeln!(out, "  {} → {}", endpoint, answer)?;
+            }
+        }
+
+        let mut header_written = false;
+        for record in self.slots.iter().filter(|r| !r.meta_diffs.is_empty()) {
+            if !header_written {
+                writeln!(out, "\n--- META DIFFS ---")?;
+                header_written = true;
+            }
+            writeln!(out, "Slot {}:", record.slot)?;
+            for diff in &record.meta_diffs {
+                writeln!(out, "  {} → {}", diff.signature, diff.fields.join(", "))?;
+            }
+        }
+        writeln!(out, "===========================================")
+    }
+
+    fn write_csv(&self, out: &mut dyn Write) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_writer(out);
+        for record in &self.slots {
+            writer.serialize(CsvRow {
+                slot: record.slot,
+                status: record.status,
+                grpc_tx_count: record.grpc_tx_count,
+                rpc_tx_count: record.rpc_tx_count,
+                latency_ms: record.latency_ms,
+                error: record.error.as_deref(),
+                missing_signatures: record.signature_diff.missing.len(),
+                extra_signatures: record.signature_diff.extra.len(),
+                duplicated_signatures: record.signature_diff.duplicated.len(),
+                meta_diffs: record.meta_diffs.len(),
+                rpc_disagreement: record.rpc_disagreement.is_some(),
+                received_at_unix_ms: record.received_at_unix_ms,
+                block_time_lag_ms: record.block_time_lag_ms,
+                rpc_lag_ms: record.rpc_lag_ms,
+            })?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SignatureDiff {
+    // present in RPC getBlock but never emitted by gRPC
+    pub missing: Vec<String>,
+    // emitted by gRPC but unknown to RPC getBlock
+    pub extra: Vec<String>,
+    // emitted by gRPC more than once, with the number of occurrences
+    pub duplicated: Vec<(String, usize)>,
+}
+
+impl SignatureDiff {
+    pub fn compute(grpc_signatures: &[String], rpc_signatures: &[String]) -> Self {
+        let mut grpc_counts: HashMap<&str, usize> = HashMap::new();
+        for sig in grpc_signatures {
+            *grpc_counts.entry(sig.as_str()).or_default() += 1;
+        }
+        let rpc_set: HashSet<&str> = rpc_signatures.iter().map(String::as_str).collect();
+
+        let mut diff = SignatureDiff::default();
+        let mut reported = HashSet::new();
+        for sig in rpc_signatures {
+            if !grpc_counts.contains_key(sig.as_str()) && reported.insert(sig.as_str()) {
+                diff.missing.push(sig.clone());
+            }
+        }
+        for sig in grpc_signatures {
+            if !reported.insert(sig.as_str()) {
+                continue;
+            }
+            if !rpc_set.contains(sig.as_str()) {
+                diff.extra.push(sig.clone());
+            }
+            let count = grpc_counts[sig.as_str()];
+            if count > 1 {
+                diff.duplicated.push((sig.clone(), count));
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.duplicated.is_empty()
+    }
+}
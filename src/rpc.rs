@@ -0,0 +1,213 @@
+use {
+    crate::{metrics, report::Report, source::ReferenceBlockSource},
+    backoff::{ExponentialBackoff, future::retry_notify},
+    log::warn,
+    serde::Serialize,
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        rpc_config::{CommitmentConfig, RpcBlockConfig, TransactionDetails},
+        rpc_request::{RpcError, RpcResponseErrorData},
+        rpc_response::UiConfirmedBlock,
+    },
+    solana_transaction_status_client_types::{EncodedTransaction, UiTransactionEncoding},
+    std::{
+        fmt,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+};
+
+// JSON-RPC server error codes returned by getBlock
+// (see solana-rpc-client-api `custom_error`)
+const BLOCK_CLEANED_UP: i64 = -32001;
+const BLOCK_NOT_AVAILABLE: i64 = -32004;
+const NODE_UNHEALTHY: i64 = -32005;
+const SLOT_SKIPPED: i64 = -32007;
+const LONG_TERM_STORAGE_SLOT_SKIPPED: i64 = -32009;
+const TRANSACTION_HISTORY_NOT_AVAILABLE: i64 = -32011;
+const BLOCK_STATUS_NOT_AVAILABLE_YET: i64 = -32014;
+const MIN_CONTEXT_SLOT_NOT_REACHED: i64 = -32016;
+const LONG_TERM_STORAGE_UNREACHABLE: i64 = -32019;
+
+// Why a slot received from gRPC could not be verified against RPC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnverifiedReason {
+    SlotSkipped,
+    LongTermStorageUnavailable,
+    BlockCleanedUp,
+    RetriesExhausted,
+    // the RPC endpoints of a quorum did not agree on the block
+    NoRpcQuorum,
+    RpcError,
+    // --deep-compare could not decode a transaction meta on either side
+    UndecodableMeta,
+}
+
+impl fmt::Display for UnverifiedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::SlotSkipped => "slot skipped",
+            Self::LongTermStorageUnavailable => "long-term storage unavailable",
+            Self::BlockCleanedUp => "block cleaned up",
+            Self::RetriesExhausted => "retries exhausted",
+            Self::NoRpcQuorum => "no rpc quorum",
+            Self::RpcError => "rpc error",
+            Self::UndecodableMeta => "undecodable meta",
+        })
+    }
+}
+
+fn response_error(code: i64, message: String) -> ClientError {
+    ClientErrorKind::RpcError(RpcError::RpcResponseError {
+        code,
+        message,
+        data: RpcResponseErrorData::Empty,
+    })
+    .into()
+}
+
+// The error RPC returns for a skipped slot, for reference sources that are not RPC.
+pub fn slot_skipped_error(slot: u64) -> ClientError {
+    response_error(
+        SLOT_SKIPPED,
+        format!(
+            "Slot {} was skipped, or missing due to ledger jump to recent snapshot",
+            slot
+        ),
+    )
+}
+
+// The error RPC returns for a block it does not have yet.
+pub fn block_not_available_error(slot: u64) -> ClientError {
+    response_error(
+        BLOCK_NOT_AVAILABLE,
+        format!("Block not available for slot {}", slot),
+    )
+}
+
+// The error RPC returns for a block it no longer keeps.
+pub fn block_cleaned_up_error(slot: u64) -> ClientError {
+    response_error(
+        BLOCK_CLEANED_UP,
+        format!("Block {} cleaned up, does not exist on node", slot),
+    )
+}
+
+// Signatures only, or every transaction with its status meta when `full`.
+pub fn block_config(full: bool) -> RpcBlockConfig {
+    RpcBlockConfig {
+        encoding: full.then_some(UiTransactionEncoding::Json),
+        transaction_details: Some(if full {
+            TransactionDetails::Full
+        } else {
+            TransactionDetails::Signatures
+        }),
+        rewards: None,
+        commitment: Some(CommitmentConfig::finalized()),
+        max_supported_transaction_version: Some(0),
+    }
+}
+
+// Transaction signatures of a getBlock response, in block order, whether it
+// was fetched with signatures only or with full transactions.
+pub fn block_signatures(block: &UiConfirmedBlock) -> Vec<String> {
+    match (&block.signatures, &block.transactions) {
+        (Some(signatures), _) => signatures.clone(),
+        (None, Some(transactions)) => transactions
+            .iter()
+            .filter_map(|tx| match &tx.transaction {
+                EncodedTransaction::Json(ui_tx) => ui_tx.signatures.first().cloned(),
+                _ => None,
+            })
+            .collect(),
+        (None, None) => vec![],
+    }
+}
+
+#[derive(Debug)]
+pub enum ErrorClass {
+    // worth retrying: the block may simply not be available yet, or RPC is throttling us
+    Transient,
+    Terminal(UnverifiedReason),
+}
+
+pub fn classify_error(err: &ClientError) -> ErrorClass {
+    match err.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => match *code {
+            BLOCK_NOT_AVAILABLE
+            | NODE_UNHEALTHY
+            | BLOCK_STATUS_NOT_AVAILABLE_YET
+            | MIN_CONTEXT_SLOT_NOT_REACHED => ErrorClass::Transient,
+            SLOT_SKIPPED | LONG_TERM_STORAGE_SLOT_SKIPPED => {
+                ErrorClass::Terminal(UnverifiedReason::SlotSkipped)
+            }
+            TRANSACTION_HISTORY_NOT_AVAILABLE | LONG_TERM_STORAGE_UNREACHABLE => {
+                ErrorClass::Terminal(UnverifiedReason::LongTermStorageUnavailable)
+            }
+            BLOCK_CLEANED_UP => ErrorClass::Terminal(UnverifiedReason::BlockCleanedUp),
+            _ => ErrorClass::Terminal(UnverifiedReason::RpcError),
+        },
+        ClientErrorKind::Reqwest(err)
+            if err.is_timeout()
+                || err.is_connect()
+                || err
+                    .status()
+                    .is_some_and(|status| status.as_u16() == 429 || status.is_server_error()) =>
+        {
+            ErrorClass::Transient
+        }
+        ClientErrorKind::Io(_) => ErrorClass::Transient,
+        _ => ErrorClass::Terminal(UnverifiedReason::RpcError),
+    }
+}
+
+// getBlock with exponential backoff on transient errors. On failure returns the
+// reason the slot could not be verified together with the last error.
+pub async fn get_block_with_retry(
+    client: &impl ReferenceBlockSource,
+    slot: u64,
+    config: RpcBlockConfig,
+    retry_timeout: Duration,
+    report: &Arc<Mutex<Report>>,
+) -> Result<UiConfirmedBlock, (UnverifiedReason, ClientError)> {
+    let policy = ExponentialBackoff {
+        max_elapsed_time: Some(retry_timeout),
+        ..Default::default()
+    };
+
+    retry_notify(
+        policy,
+        || async move {
+            client
+                .get_block(slot, config)
+                .await
+                .map_err(|e| match classify_error(&e) {
+                    ErrorClass::Transient => backoff::Error::transient(e),
+                    ErrorClass::Terminal(_) => backoff::Error::permanent(e),
+                })
+        },
+        |e: ClientError, after: Duration| {
+            let mut rep = report.lock().unwrap();
+            rep.rpc_retries += 1;
+            metrics::RPC_ERRORS
+                .with_label_values(&[&rep.provider])
+                .inc();
+            warn!(
+                "RPC getBlock slot {} failed, retrying in {:?}: {}",
+                slot, after, e
+            );
+        },
+    )
+    .await
+    .inspect_err(|_| {
+        let rep = report.lock().unwrap();
+        metrics::RPC_ERRORS
+            .with_label_values(&[&rep.provider])
+            .inc();
+    })
+    .map_err(|e| match classify_error(&e) {
+        ErrorClass::Transient => (UnverifiedReason::RetriesExhausted, e),
+        ErrorClass::Terminal(reason) => (reason, e),
+    })
+}
@@ -0,0 +1,57 @@
+use {
+    log::{error, info},
+    tokio::{signal, sync::watch, time::Instant},
+};
+
+// Flips once SIGINT or SIGTERM is received. Cloned into every run so the
+// subscription can stop and the final report still gets written.
+#[derive(Debug, Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    pub fn listen() -> Self {
+        let (tx, rx) = watch::channel(false);
+        tokio::spawn(async move {
+            let name = tokio::select! {
+                _ = signal::ctrl_c() => "SIGINT",
+                _ = terminate() => "SIGTERM",
+            };
+            info!("Received {}, shutting down...", name);
+            let _ = tx.send(true);
+        });
+        Self(rx)
+    }
+
+    pub async fn wait(&mut self) {
+        if self.0.wait_for(|stop| *stop).await.is_err() {
+            // the listener is gone, so no signal can arrive anymore
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+// Sleeps until `deadline`, or forever when there is none.
+pub async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(unix)]
+async fn terminate() {
+    match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+        Ok(mut sigterm) => {
+            sigterm.recv().await;
+        }
+        Err(e) => {
+            error!("Failed to install SIGTERM handler: {:?}", e);
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminate() {
+    std::future::pending::<()>().await;
+}
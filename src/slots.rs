@@ -0,0 +1,269 @@
+use {
+    log::warn,
+    serde::Serialize,
+    std::{collections::BTreeMap, fmt},
+    yellowstone_grpc_proto::geyser::{SlotStatus, SubscribeUpdateSlot},
+};
+
+// slots followed behind the highest slot seen, well past the 32 slots it takes
+// a slot to be finalized
+const RETAINED_SLOTS: u64 = 300;
+
+// commitment statuses in the order a slot reaches them
+const COMMITMENT: [SlotStatus; 3] = [
+    SlotStatus::SlotProcessed,
+    SlotStatus::SlotConfirmed,
+    SlotStatus::SlotFinalized,
+];
+
+// Position of a status in a slot's lifecycle, None for a dead slot. The
+// interslot statuses share the first stage, their order is not guaranteed.
+fn stage(status: SlotStatus) -> Option<usize> {
+    match status {
+        SlotStatus::SlotFirstShredReceived
+        | SlotStatus::SlotCreatedBank
+        | SlotStatus::SlotCompleted => Some(0),
+        SlotStatus::SlotProcessed => Some(1),
+        SlotStatus::SlotConfirmed => Some(2),
+        SlotStatus::SlotFinalized => Some(3),
+        SlotStatus::SlotDead => None,
+    }
+}
+
+fn name(status: SlotStatus) -> &'static str {
+    match status {
+        SlotStatus::SlotProcessed => "processed",
+        SlotStatus::SlotConfirmed => "confirmed",
+        SlotStatus::SlotFinalized => "finalized",
+        SlotStatus::SlotFirstShredReceived => "first shred received",
+        SlotStatus::SlotCompleted => "completed",
+        SlotStatus::SlotCreatedBank => "created bank",
+        SlotStatus::SlotDead => "dead",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlotAnomalyKind {
+    // a commitment status arrived without the ones before it
+    SkippedStatus,
+    // a status arrived after a later one, or a slot was both dead and confirmed
+    Regression,
+    // finalized on the slot stream but never delivered on the block stream
+    Undelivered,
+}
+
+impl fmt::Display for SlotAnomalyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::SkippedStatus => "skipped status",
+            Self::Regression => "regression",
+            Self::Undelivered => "undelivered",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SlotAnomaly {
+    pub slot: u64,
+    pub kind: SlotAnomalyKind,
+    pub detail: String,
+}
+
+// What the slot status stream reported alongside the blocks.
+#[derive(Debug, Default, Serialize)]
+pub struct SlotStatusStats {
+    pub updates: u64,
+    pub finalized_slots: u64,
+    pub dead_slots: u64,
+    pub skipped_statuses: u64,
+    pub regressions: u64,
+    pub undelivered_slots: u64,
+    // the anomalies themselves, which a daemon window takes along
+    pub anomalies: Vec<SlotAnomaly>,
+}
+
+impl SlotStatusStats {
+    pub fn count(&self, kind: SlotAnomalyKind) -> u64 {
+        match kind {
+            SlotAnomalyKind::SkippedStatus => self.skipped_statuses,
+            SlotAnomalyKind::Regression => self.regressions,
+            SlotAnomalyKind::Undelivered => self.undelivered_slots,
+        }
+    }
+
+    pub fn anomaly_count(&self) -> u64 {
+        self.skipped_statuses + self.regressions + self.undelivered_slots
+    }
+
+    fn flag(&mut self, slot: u64, kind: SlotAnomalyKind, detail: String) {
+        warn!("SLOT STATUS {} slot {} → {}", kind, slot, detail);
+        *match kind {
+            SlotAnomalyKind::SkippedStatus => &mut self.skipped_statuses,
+            SlotAnomalyKind::Regression => &mut self.regressions,
+            SlotAnomalyKind::Undelivered => &mut self.undelivered_slots,
+        } += 1;
+        self.anomalies.push(SlotAnomaly { slot, kind, detail });
+    }
+}
+
+#[derive(Debug, Default)]
+struct SlotState {
+    // the status of the furthest stage reached
+    status: Option<SlotStatus>,
+    dead: bool,
+    delivered: bool,
+}
+
+impl SlotState {
+    fn stage(&self) -> Option<usize> {
+        self.status.and_then(stage)
+    }
+}
+
+// Follows the status progression of every recent slot on the slot stream and
+// which of them the block stream delivered.
+#[derive(Debug, Default)]
+pub struct SlotStatusTracker {
+    slots: BTreeMap<u64, SlotState>,
+    // first slot whose lifecycle started after the stream (re)started, earlier
+    // ones may have statuses from before it
+    judged_from: Option<u64>,
+    highest: u64,
+    highest_delivered: Option<u64>,
+}
+
+impl SlotStatusTracker {
+    pub fn record(&mut self, update: &SubscribeUpdateSlot, stats: &mut SlotStatusStats) {
+        let slot = update.slot;
+        let Ok(status) = SlotStatus::try_from(update.status) else {
+            return;
+        };
+        stats.updates += 1;
+        if slot + RETAINED_SLOTS < self.highest {
+            return;
+        }
+        if self.judged_from.is_none() && stage(status).is_some_and(|stage| stage <= 1) {
+            self.judged_from = Some(slot);
+        }
+        let judged = self.judged(slot);
+        let state = self.slots.entry(slot).or_default();
+
+        match stage(status) {
+            None => {
+                if !state.dead {
+                    state.dead = true;
+                    stats.dead_slots += 1;
+                }
+                if let Some(reached) = state.status
+                    && state.stage() >= Some(2)
+                {
+                    let detail = match &update.dead_error {
+                        Some(error) => format!("dead ({}) after {}", error, name(reached)),
+                        None => format!("dead after {}", name(reached)),
+                    };
+                    stats.flag(slot, SlotAnomalyKind::Regression, detail);
+                }
+            }
+            Some(stage) => {
+                if state.dead && stage >= 2 {
+                    stats.flag(
+                        slot,
+                        SlotAnomalyKind::Regression,
+                        format!("{} after dead", name(status)),
+                    );
+                }
+                match state.status {
+                    // late interslot statuses are not ordered against the commitment ones
+                    Some(_) if stage == 0 => {}
+                    Some(reached) if state.stage() > Some(stage) => stats.flag(
+                        slot,
+                        SlotAnomalyKind::Regression,
+                        format!("{} after {}", name(status), name(reached)),
+                    ),
+                    Some(_) if state.stage() == Some(stage) => {}
+                    _ => {
+                        let reached = state.stage().unwrap_or_default();
+                        let skipped: Vec<_> = ((reached + 1).max(1)..stage)
+                            .map(|stage| name(COMMITMENT[stage - 1]))
+                            .collect();
+                        if judged && !skipped.is_empty() {
+                            stats.flag(
+                                slot,
+                                SlotAnomalyKind::SkippedStatus,
+                                format!("{} without {}", name(status), skipped.join(", ")),
+                            );
+                        }
+                        if status == SlotStatus::SlotFinalized {
+                            stats.finalized_slots += 1;
+                        }
+                        state.status = Some(status);
+                    }
+                }
+            }
+        }
+
+        if slot > self.highest {
+            self.highest = slot;
+            self.prune(stats);
+        }
+    }
+
+    // Marks `slot` as delivered on the block stream.
+    pub fn delivered(&mut self, slot: u64) {
+        self.highest_delivered = self.highest_delivered.max(Some(slot));
+        if slot + RETAINED_SLOTS >= self.highest {
+            self.slots.entry(slot).or_default().delivered = true;
+        }
+    }
+
+    // Forgets the slots a `from_slot` replay delivers again and stops judging
+    // the slots in flight, whose statuses the reconnect may have cut.
+    pub fn restart(&mut self, from_slot: Option<u64>) {
+        if let Some(from_slot) = from_slot {
+            self.slots.split_off(&from_slot);
+        }
+        self.judged_from = None;
+    }
+
+    // Judges the slots still followed once the stream is over.
+    pub fn finish(&mut self, stats: &mut SlotStatusStats) {
+        let slots = std::mem::take(&mut self.slots);
+        self.check_delivered(slots, stats);
+    }
+
+    fn judged(&self, slot: u64) -> bool {
+        self.judged_from.is_some_and(|from| slot >= from)
+    }
+
+    fn prune(&mut self, stats: &mut SlotStatusStats) {
+        let retained_from = self.highest.saturating_sub(RETAINED_SLOTS);
+        if self
+            .slots
+            .first_key_value()
+            .is_some_and(|(oldest, _)| *oldest < retained_from)
+        {
+            let retained = self.slots.split_off(&retained_from);
+            let pruned = std::mem::replace(&mut self.slots, retained);
+            self.check_delivered(pruned, stats);
+        }
+    }
+
+    // Flags the finalized slots the block stream went past without delivering.
+    fn check_delivered(&self, slots: BTreeMap<u64, SlotState>, stats: &mut SlotStatusStats) {
+        for (slot, state) in slots {
+            if state.status == Some(SlotStatus::SlotFinalized)
+                && !state.delivered
+                && self.judged(slot)
+                && self.highest_delivered.is_some_and(|highest| slot < highest)
+            {
+                stats.flag(
+                    slot,
+                    SlotAnomalyKind::Undelivered,
+                    "finalized on the slot stream but never delivered on the block stream"
+                        .to_string(),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use {
+    crate::{quorum::RpcDisagreement, shutdown::sleep_until},
+    backoff::{ExponentialBackoff, backoff::Backoff},
+    futures::{Sink, SinkExt, StreamExt, channel::mpsc, stream::BoxStream},
+    log::warn,
+    solana_client::{
+        client_error::ClientError,
+        nonblocking::rpc_client::RpcClient,
+        rpc_config::{CommitmentConfig, RpcAccountInfoConfig, RpcBlockConfig},
+        rpc_response::{Response, UiAccount, UiAccountEncoding, UiConfirmedBlock},
+    },
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        future::Future,
+        pin::Pin,
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::Duration,
+    },
+    tokio::time::Instant,
+    tonic::Status,
+    yellowstone_grpc_client::{ClientTlsConfig, GeyserGrpcClient, Interceptor},
+    yellowstone_grpc_proto::geyser::{
+        SubscribeRequest, SubscribeRequestPing, SubscribeUpdate, subscribe_update::UpdateOneof,
+    },
+};
+
+pub type UpdateSink = Pin<Box<dyn Sink<SubscribeRequest, Error = anyhow::Error> + Send>>;
+pub type UpdateStream = BoxStream<'static, Result<SubscribeUpdate, Status>>;
+
+// Where verified blocks come from. Every call opens a new subscription, so
+// implementations are expected to reconnect.
+pub trait BlockStreamSource: Send + Sync {
+    // Subscribes with `request` and returns the sink used to answer pings
+    // together with the update stream.
+    fn subscribe(
+        &self,
+        request: SubscribeRequest,
+    ) -> impl Future<Output = anyhow::Result<(UpdateSink, UpdateStream)>> + Send;
+}
+
+// Why a subscription stopped delivering updates.
+#[derive(Debug)]
+pub enum Interruption {
+    Ended,
+    // no data arrived for this long
+    Stalled(Duration),
+    Failed(Status),
+}
+
+// Reconnects a stream for as long as the run lasts. The backoff between
+// attempts starts over once a subscription has delivered data, so a stream
+// that ran for hours before dropping reconnects right away.
+pub struct Reconnects {
+    // what is reconnecting, for the logs
+    name: &'static str,
+    delivered: Arc<AtomicBool>,
+}
+
+impl Reconnects {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            delivered: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Opens a subscription whose data resets the backoff.
+    pub async fn subscribe(
+        &self,
+        source: &impl BlockStreamSource,
+        request: SubscribeRequest,
+        stall_timeout: Option<Duration>,
+    ) -> anyhow::Result<Subscription> {
+        let (tx, stream) = source.subscribe(request).await?;
+        Ok(Subscription {
+            tx,
+            stream,
+            stall_timeout,
+            last_data: Instant::now(),
+            delivered: self.delivered.clone(),
+        })
+    }
+
+    // Runs `attempt` until it succeeds or fails permanently.
+    pub async fn run<T, F, Fut>(&self, mut attempt: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, backoff::Error<anyhow::Error>>>,
+    {
+        let mut backoff = ExponentialBackoff {
+            max_elapsed_time: None,
+            ..Default::default()
+        };
+        loop {
+            let (e, retry_after) = match attempt().await {
+                Ok(value) => return Ok(value),
+                Err(backoff::Error::Permanent(e)) => return Err(e),
+                Err(backoff::Error::Transient { err, retry_after }) => (err, retry_after),
+            };
+            if self.delivered.swap(false, Ordering::Relaxed) {
+                backoff.reset();
+            }
+            // without an elapsed time limit the backoff never runs out
+            let wait = retry_after
+                .or_else(|| backoff.next_backoff())
+                .unwrap_or(backoff.max_interval);
+            warn!("{} interrupted, retrying in {:?}: {:#}", self.name, wait, e);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+// One subscription to a `BlockStreamSource`. Pings are answered as they
+// arrive, and with a stall timeout a subscription that delivers no data for
+// that long is interrupted so the caller can reconnect.
+pub struct Subscription {
+    tx: UpdateSink,
+    stream: UpdateStream,
+    stall_timeout: Option<Duration>,
+    last_data: Instant,
+    delivered: Arc<AtomicBool>,
+}
+
+impl Subscription {
+    // The next update, pings included.
+    pub async fn next(&mut self) -> Result<SubscribeUpdate, Interruption> {
+        let stall_at = self.stall_timeout.map(|timeout| self.last_data + timeout);
+        let msg = tokio::select! {
+            msg = self.stream.next() => msg,
+            _ = sleep_until(stall_at) => {
+                return Err(Interruption::Stalled(self.last_data.elapsed()));
+            }
+        };
+        let update = match msg {
+            Some(Ok(update)) => update,
+            Some(Err(status)) => return Err(Interruption::Failed(status)),
+            None => return Err(Interruption::Ended),
+        };
+
+        if let Some(UpdateOneof::Ping(_)) = update.update_oneof {
+            let _ = self
+                .tx
+                .send(SubscribeRequest {
+                    ping: Some(SubscribeRequestPing { id: 1 }),
+                    ..Default::default()
+                })
+                .await;
+        }
+        Ok(update)
+    }
+
+    // Marks that the update just returned carried the data the stream is
+    // for. Pings and other bookkeeping updates keep neither the stall
+    // watchdog nor the reconnect backoff from firing.
+    pub fn received_data(&mut self) {
+        self.last_data = Instant::now();
+        self.delivered.store(true, Ordering::Relaxed);
+    }
+}
+
+// The "truth" blocks are compared against. Errors are RPC errors so they can
+// be classified the same way whatever the source.
+pub trait ReferenceBlockSource: Send + Sync + 'static {
+    fn get_block(
+        &self,
+        slot: u64,
+        config: RpcBlockConfig,
+    ) -> impl Future<Output = Result<UiConfirmedBlock, ClientError>> + Send;
+
+    // Finalized blocks in `[start, end]`.
+    fn get_blocks(
+        &self,
+        start: u64,
+        end: u64,
+    ) -> impl Future<Output = Result<Vec<u64>, ClientError>> + Send;
+
+    // Latest finalized slot.
+    fn get_slot(&self) -> impl Future<Output = Result<u64, ClientError>> + Send;
+
+    // How the endpoints behind the source disagreed on the last `get_block` of
+    // `slot`, for sources that query several of them.
+    fn take_disagreement(&self, _slot: u64) -> Option<RpcDisagreement> {
+        None
+    }
+}
+
+// The "truth" account states are compared against.
+pub trait ReferenceAccountSource: Send + Sync {
+    // Finalized states of `pubkeys` as of `min_context_slot` or later, with
+    // the data base64+zstd encoded.
+    fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+        min_context_slot: u64,
+    ) -> impl Future<Output = Result<Response<Vec<Option<UiAccount>>>, ClientError>> + Send;
+}
+
+// A Yellowstone gRPC endpoint.
+#[derive(Debug, Clone)]
+pub struct GeyserSource {
+    pub endpoint: String,
+    pub x_token: Option<String>,
+}
+
+impl GeyserSource {
+    pub async fn connect(&self) -> anyhow::Result<GeyserGrpcClient<impl Interceptor + use<>>> {
+        let client = GeyserGrpcClient::build_from_shared(self.endpoint.to_owned())?
+            .x_token(self.x_token.to_owned())?
+            .tls_config(ClientTlsConfig::new().with_native_roots())?
+            .connect()
+            .await?;
+        Ok(client)
+    }
+}
+
+impl BlockStreamSource for GeyserSource {
+    async fn subscribe(
+        &self,
+        request: SubscribeRequest,
+    ) -> anyhow::Result<(UpdateSink, UpdateStream)> {
+        let mut client = self.connect().await?;
+        // the response stream owns its channel, so it outlives the client
+        let (tx, rx) = mpsc::unbounded();
+        tx.unbounded_send(request)?;
+        let stream = client.geyser.subscribe(rx).await?.into_inner();
+        Ok((
+            Box::pin(tx.sink_map_err(anyhow::Error::from)),
+            stream.boxed(),
+        ))
+    }
+}
+
+impl ReferenceBlockSource for RpcClient {
+    async fn get_block(
+        &self,
+        slot: u64,
+        config: RpcBlockConfig,
+    ) -> Result<UiConfirmedBlock, ClientError> {
+        self.get_block_with_config(slot, config).await
+    }
+
+    async fn get_blocks(&self, start: u64, end: u64) -> Result<Vec<u64>, ClientError> {
+        self.get_blocks_with_commitment(start, Some(end), CommitmentConfig::finalized())
+            .await
+    }
+
+    async fn get_slot(&self) -> Result<u64, ClientError> {
+        self.get_slot_with_commitment(CommitmentConfig::finalized())
+            .await
+    }
+}
+
+impl<R: ReferenceBlockSource> ReferenceBlockSource for Arc<R> {
+    async fn get_block(
+        &self,
+        slot: u64,
+        config: RpcBlockConfig,
+    ) -> Result<UiConfirmedBlock, ClientError> {
+        self.as_ref().get_block(slot, config).await
+    }
+
+    async fn get_blocks(&self, start: u64, end: u64) -> Result<Vec<u64>, ClientError> {
+        self.as_ref().get_blocks(start, end).await
+    }
+
+    async fn get_slot(&self) -> Result<u64, ClientError> {
+        self.as_ref().get_slot().await
+    }
+
+    fn take_disagreement(&self, slot: u64) -> Option<RpcDisagreement> {
+        self.as_ref().take_disagreement(slot)
+    }
+}
+
+impl ReferenceAccountSource for RpcClient {
+    async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+        min_context_slot: u64,
+    ) -> Result<Response<Vec<Option<UiAccount>>>, ClientError> {
+        self.get_multiple_ui_accounts_with_config(
+            pubkeys,
+            RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64Zstd),
+                data_slice: None,
+                commitment: Some(CommitmentConfig::finalized()),
+                min_context_slot: Some(min_context_slot),
+            },
+        )
+        .await
+    }
+}
@@ -0,0 +1,320 @@
+use {
+    log::error,
+    solana_grpc_integrity_checker::{Report, accounts::AccountReport, providers::ProviderReport},
+    std::process::ExitCode,
+};
+
+// Limits that turn a completed run into a failure, so the checker can gate
+// rollouts in CI. Unset limits are not enforced.
+#[derive(Debug, Clone, Copy, Default, clap::Args)]
+pub struct Thresholds {
+    #[clap(long)]
+    pub max_mismatched_blocks: Option<u64>,
+    // mismatched blocks / verified blocks, between 0.0 and 1.0
+    #[clap(long)]
+    pub max_mismatch_ratio: Option<f64>,
+    #[clap(long)]
+    pub max_missing_blocks: Option<u64>,
+    #[clap(long)]
+    pub max_unverified_slots: Option<u64>,
+    // skipped, regressed or undelivered slots on the slot status stream
+    #[clap(long)]
+    pub max_slot_status_anomalies: Option<u64>,
+    // accounts mode
+    #[clap(long)]
+    pub max_mismatched_accounts: Option<u64>,
+    #[clap(long)]
+    pub max_inconclusive_accounts: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    MismatchedBlocks,
+    MismatchRatio,
+    MissingBlocks,
+    UnverifiedSlots,
+    SlotStatusAnomalies,
+    MismatchedAccounts,
+    InconclusiveAccounts,
+}
+
+impl Violation {
+    // 1 is left to runtime errors returned from `main`
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::MismatchedBlocks => 2,
+            Self::MismatchRatio => 3,
+            Self::MissingBlocks => 4,
+            Self::UnverifiedSlots => 5,
+            Self::SlotStatusAnomalies => 6,
+            Self::MismatchedAccounts => 7,
+            Self::InconclusiveAccounts => 8,
+        }
+    }
+}
+
+impl Thresholds {
+    pub fn check(&self, report: &Report) -> Vec<(Violation, String)> {
+        let mut violations = vec![];
+
+        if let Some(max) = self.max_mismatched_blocks
+            && report.mismatched_blocks > max
+        {
+            violations.push((
+                Violation::MismatchedBlocks,
+                format!("{} mismatched blocks > {}", report.mismatched_blocks, max),
+            ));
+        }
+
+        if let Some(max) = self.max_mismatch_ratio {
+            let ratio = if report.verified_blocks == 0 {
+                0.0
+            } else {
+                report.mismatched_blocks as f64 / report.verified_blocks as f64
+            };
+            if ratio > max {
+                violations.push((
+                    Violation::MismatchRatio,
+                    format!("mismatch ratio {:.4} > {}", ratio, max),
+                ));
+            }
+        }
+
+        if let Some(max) = self.max_missing_blocks
+            && report.missing_blocks > max
+        {
+            violations.push((
+                Violation::MissingBlocks,
+                format!("{} missing blocks > {}", report.missing_blocks, max),
+            ));
+        }
+
+        if let Some(max) = self.max_unverified_slots
+            && report.unverified_slots > max
+        {
+            violations.push((
+                Violation::UnverifiedSlots,
+                format!("{} unverified slots > {}", report.unverified_slots, max),
+            ));
+        }
+
+        let anomalies = report.slot_status.anomaly_count();
+        if let Some(max) = self.max_slot_status_anomalies
+            && anomalies > max
+        {
+            violations.push((
+                Violation::SlotStatusAnomalies,
+                format!("{} slot status anomalies > {}", anomalies, max),
+            ));
+        }
+
+        violations
+    }
+
+    pub fn check_accounts(&self, report: &AccountReport) -> Vec<(Violation, String)> {
+        let mut violations = vec![];
+
+        if let Some(max) = self.max_mismatched_accounts
+            && report.mismatched_accounts > max
+        {
+            violations.push((
+                Violation::MismatchedAccounts,
+                format!(
+                    "{} mismatched accounts > {}",
+                    report.mismatched_accounts, max
+                ),
+            ));
+        }
+
+        if let Some(max) = self.max_inconclusive_accounts
+            && report.inconclusive_accounts > max
+        {
+            violations.push((
+                Violation::InconclusiveAccounts,
+                format!(
+                    "{} inconclusive accounts > {}",
+                    report.inconclusive_accounts, max
+                ),
+            ));
+        }
+
+        violations
+    }
+
+    // Logs every exceeded threshold and returns the exit code of the first one.
+    pub fn exit_code(&self, report: &Report) -> ExitCode {
+        first_violation(self.check(report))
+    }
+
+    // Like `exit_code`, for the `accounts` subcommand.
+    pub fn exit_code_for_accounts(&self, report: &AccountReport) -> ExitCode {
+        first_violation(self.check_accounts(report))
+    }
+
+    // Like `exit_code`, over the reports of every compared provider.
+    pub fn exit_code_for_providers(&self, providers: &[ProviderReport]) -> ExitCode {
+        let mut first = None;
+        for provider in providers {
+            for (violation, message) in self.check(&provider.report) {
+                error!("THRESHOLD EXCEEDED ({}) → {}", provider.endpoint, message);
+                first.get_or_insert(violation);
+            }
+        }
+        first.map_or(ExitCode::SUCCESS, |violation| {
+            ExitCode::from(violation.exit_code())
+        })
+    }
+}
+
+fn first_violation(violations: Vec<(Violation, String)>) -> ExitCode {
+    for (_, message) in &violations {
+        error!("THRESHOLD EXCEEDED → {}", message);
+    }
+    violations
+        .first()
+        .map_or(ExitCode::SUCCESS, |(violation, _)| {
+            ExitCode::from(violation.exit_code())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn violated(thresholds: &Thresholds, report: &Report) -> Vec<Violation> {
+        thresholds
+            .check(report)
+            .into_iter()
+            .map(|(violation, _)| violation)
+            .collect()
+    }
+
+    fn violated_accounts(thresholds: &Thresholds, report: &AccountReport) -> Vec<Violation> {
+        thresholds
+            .check_accounts(report)
+            .into_iter()
+            .map(|(violation, _)| violation)
+            .collect()
+    }
+
+    #[test]
+    fn unset_thresholds_are_not_enforced() {
+        let report = Report {
+            verified_blocks: 10,
+            mismatched_blocks: 10,
+            missing_blocks: 10,
+            unverified_slots: 10,
+            ..Default::default()
+        };
+        assert!(violated(&Thresholds::default(), &report).is_empty());
+    }
+
+    #[test]
+    fn mismatched_blocks() {
+        let thresholds = Thresholds {
+            max_mismatched_blocks: Some(1),
+            ..Default::default()
+        };
+        let mut report = Report::default();
+        assert!(violated(&thresholds, &report).is_empty());
+        report.mismatched_blocks = 1;
+        assert!(violated(&thresholds, &report).is_empty());
+        report.mismatched_blocks = 2;
+        assert_eq!(
+            violated(&thresholds, &report),
+            [Violation::MismatchedBlocks]
+        );
+    }
+
+    #[test]
+    fn mismatch_ratio() {
+        let thresholds = Thresholds {
+            max_mismatch_ratio: Some(0.1),
+            ..Default::default()
+        };
+        // nothing verified is no mismatch
+        let mut report = Report::default();
+        assert!(violated(&thresholds, &report).is_empty());
+        report.verified_blocks = 10;
+        report.mismatched_blocks = 1;
+        assert!(violated(&thresholds, &report).is_empty());
+        report.mismatched_blocks = 2;
+        assert_eq!(violated(&thresholds, &report), [Violation::MismatchRatio]);
+    }
+
+    #[test]
+    fn missing_blocks() {
+        let thresholds = Thresholds {
+            max_missing_blocks: Some(0),
+            ..Default::default()
+        };
+        let mut report = Report::default();
+        assert!(violated(&thresholds, &report).is_empty());
+        report.missing_blocks = 1;
+        assert_eq!(violated(&thresholds, &report), [Violation::MissingBlocks]);
+    }
+
+    #[test]
+    fn unverified_slots() {
+        let thresholds = Thresholds {
+            max_unverified_slots: Some(2),
+            ..Default::default()
+        };
+        let mut report = Report::default();
+        assert!(violated(&thresholds, &report).is_empty());
+        report.unverified_slots = 2;
+        assert!(violated(&thresholds, &report).is_empty());
+        report.unverified_slots = 3;
+        assert_eq!(violated(&thresholds, &report), [Violation::UnverifiedSlots]);
+    }
+
+    #[test]
+    fn slot_status_anomalies() {
+        let thresholds = Thresholds {
+            max_slot_status_anomalies: Some(1),
+            ..Default::default()
+        };
+        let mut report = Report::default();
+        assert!(violated(&thresholds, &report).is_empty());
+        report.slot_status.skipped_statuses = 1;
+        assert!(violated(&thresholds, &report).is_empty());
+        // every kind counts towards the one limit
+        report.slot_status.undelivered_slots = 1;
+        assert_eq!(
+            violated(&thresholds, &report),
+            [Violation::SlotStatusAnomalies]
+        );
+    }
+
+    #[test]
+    fn mismatched_accounts() {
+        let thresholds = Thresholds {
+            max_mismatched_accounts: Some(0),
+            ..Default::default()
+        };
+        let mut report = AccountReport::default();
+        assert!(violated_accounts(&thresholds, &report).is_empty());
+        report.mismatched_accounts = 1;
+        assert_eq!(
+            violated_accounts(&thresholds, &report),
+            [Violation::MismatchedAccounts]
+        );
+    }
+
+    #[test]
+    fn inconclusive_accounts() {
+        let thresholds = Thresholds {
+            max_inconclusive_accounts: Some(1),
+            ..Default::default()
+        };
+        let mut report = AccountReport::default();
+        assert!(violated_accounts(&thresholds, &report).is_empty());
+        report.inconclusive_accounts = 1;
+        assert!(violated_accounts(&thresholds, &report).is_empty());
+        report.inconclusive_accounts = 2;
+        assert_eq!(
+            violated_accounts(&thresholds, &report),
+            [Violation::InconclusiveAccounts]
+        );
+    }
+}
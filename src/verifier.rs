@@ -0,0 +1,622 @@
+use {
+    crate::{
+        forks::ForkTracker,
+        gaps::{GapTracker, check_gaps},
+        latency::unix_ms,
+        meta::{MetaDiff, TxMetaSnapshot},
+        metrics,
+        pipeline::{VerifyJob, Workers, drain_workers, enqueue, spawn_workers},
+        providers::ProviderComparison,
+        record::{Recorder, Recording},
+        report::{BlockStatus, Report, SignatureDiff, SlotRecord},
+        rpc::{UnverifiedReason, block_config, block_signatures, get_block_with_retry},
+        shutdown::Shutdown,
+        slots::{SlotStatusStats, SlotStatusTracker},
+        source::{BlockStreamSource, Interruption, Reconnects, ReferenceBlockSource},
+    },
+    anyhow::Context,
+    log::{error, info, warn},
+    solana_client::rpc_response::UiConfirmedBlock,
+    solana_transaction_status_client_types::EncodedTransaction,
+    std::{
+        collections::HashMap,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::{Duration, SystemTime},
+    },
+    tokio::{
+        sync::mpsc::{self, Sender},
+        time::Instant,
+    },
+    tonic::Code,
+    yellowstone_grpc_proto::geyser::{
+        CommitmentLevel, SubscribeRequest, SubscribeUpdateBlock, SubscribeUpdateSlot,
+        SubscribeUpdateTransactionInfo, subscribe_update::UpdateOneof,
+    },
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct VerifierConfig {
+    // fetch full blocks from the reference and compare every transaction's meta
+    pub deep_compare: bool,
+    // how long a getBlock is retried on transient errors before the slot is left unverified
+    pub rpc_retry_timeout: Duration,
+    // concurrent reference verifications
+    pub rpc_workers: usize,
+    // blocks buffered between the stream and the workers
+    pub queue_size: usize,
+    pub gap_check_interval: Duration,
+    // reconnect when no update arrives for this long
+    pub stall_timeout: Option<Duration>,
+    // how long in-flight verifications may still run after a shutdown signal
+    pub shutdown_timeout: Duration,
+    // commitment of the stream, blocks below finalized may still be rolled back
+    pub commitment: CommitmentLevel,
+    // how long after arrival a block below finalized commitment is first looked
+    // up, as the reference only serves finalized blocks
+    pub finalization_delay: Duration,
+}
+
+// Everything between a received block and the final report: duplicate and gap
+// tracking, the verification worker pool and the report itself. Shared by the
+// live stream, backfills and recorded replays.
+pub struct Verifier<R> {
+    pub report: Arc<Mutex<Report>>,
+    pub reference: Arc<R>,
+    pub gaps: Arc<Mutex<GapTracker>>,
+    forks: Mutex<ForkTracker>,
+    slot_statuses: Mutex<SlotStatusTracker>,
+    config: VerifierConfig,
+    // a backfill's slot range, the stream stops once it is complete
+    range: Option<(u64, u64)>,
+    // this verifier's provider index, when several providers are compared
+    comparison: Option<(usize, Arc<Mutex<ProviderComparison>>)>,
+    jobs: Sender<VerifyJob>,
+    workers: Workers,
+    // the `provider` label of the metrics
+    provider: String,
+}
+
+impl<R: ReferenceBlockSource> Verifier<R> {
+    pub fn new(config: VerifierConfig, reference: R, range: Option<(u64, u64)>) -> Self {
+        let report = Arc::new(Mutex::new(Report {
+            backfill_range: range,
+            ..Default::default()
+        }));
+        let gaps = match range {
+            Some((first, _)) => GapTracker::starting_at(first),
+            None => GapTracker::default(),
+        };
+        let reference = Arc::new(reference);
+
+        {
+            let mut rep = report.lock().unwrap();
+            rep.backpressure.workers = config.rpc_workers;
+            rep.backpressure.queue_capacity = config.queue_size;
+        }
+        let (jobs, jobs_rx) = mpsc::channel(config.queue_size);
+        let workers = spawn_workers(
+            config.rpc_workers,
+            jobs_rx,
+            reference.clone(),
+            config,
+            report.clone(),
+        );
+
+        Self {
+            report,
+            reference,
+            gaps: Arc::new(Mutex::new(gaps)),
+            forks: Mutex::new(ForkTracker::default()),
+            slot_statuses: Mutex::new(SlotStatusTracker::default()),
+            config,
+            range,
+            comparison: None,
+            jobs,
+            workers,
+            provider: String::new(),
+        }
+    }
+
+    // Labels the metrics of this verifier with `provider`.
+    pub fn labelled(mut self, provider: &str) -> Self {
+        metrics::init_provider(provider);
+        self.provider = provider.to_string();
+        self.report.lock().unwrap().provider = provider.to_string();
+        self
+    }
+
+    // Also reports every new block to `comparison`, as delivered by `provider`.
+    pub fn compare_as(
+        mut self,
+        provider: usize,
+        comparison: Arc<Mutex<ProviderComparison>>,
+    ) -> Self {
+        self.comparison = Some((provider, comparison));
+        self
+    }
+
+    // Subscribes to `source` and verifies every block it delivers, reconnecting
+    // with `from_slot` replay after errors and stalls. Returns once a backfill
+    // range is complete; a live stream only returns on a permanent error.
+    pub async fn stream(
+        &self,
+        source: &impl BlockStreamSource,
+        request: SubscribeRequest,
+        recorder: Option<Recorder>,
+    ) -> anyhow::Result<()> {
+        let recorder = Mutex::new(recorder);
+        // set when the provider refused `from_slot`, so the next attempt subscribes live
+        let skip_replay = AtomicBool::new(false);
+        let reconnects = Reconnects::new("Stream");
+
+        reconnects
+            .run(|| {
+                let mut request = request.clone();
+                let (recorder, skip_replay, reconnects) = (&recorder, &skip_replay, &reconnects);
+                async move {
+                    let report = &self.report;
+                    let last_processed = self.gaps.lock().unwrap().highest_seen();
+                    if let Some(last_processed) = last_processed {
+                        report.lock().unwrap().reconnects += 1;
+                        metrics::RECONNECTS
+                            .with_label_values(&[&self.provider])
+                            .inc();
+                        if !skip_replay.swap(false, Ordering::Relaxed) {
+                            request.from_slot = Some(last_processed + 1);
+                        }
+                        self.slot_statuses
+                            .lock()
+                            .unwrap()
+                            .restart(request.from_slot);
+                        match self.reference.get_slot().await {
+                            Ok(tip) if tip > last_processed => {
+                                report
+                                    .lock()
+                                    .unwrap()
+                                    .replay_windows
+                                    .push((last_processed + 1, tip));
+                            }
+                            Ok(_) => {}
+                            Err(e) => error!("RPC getSlot error: {:?}", e),
+                        }
+                        info!(
+                            "Reconnecting after slot {} (from_slot={:?})",
+                            last_processed, request.from_slot
+                        );
+                    }
+
+                    let mut subscription = reconnects
+                        .subscribe(source, request.clone(), self.config.stall_timeout)
+                        .await
+                        .map_err(backoff::Error::transient)?;
+
+                    loop {
+                        let update = match subscription.next().await {
+                            Ok(update) => update,
+                            Err(Interruption::Ended) => break,
+                            Err(Interruption::Stalled(quiet)) => {
+                                warn!("STALL → no block for {:?}, reconnecting", quiet);
+                                report.lock().unwrap().stalls += 1;
+                                metrics::STALLS.with_label_values(&[&self.provider]).inc();
+                                break;
+                            }
+                            Err(Interruption::Failed(status)) => {
+                                if request.from_slot.is_some()
+                                    && status.code() == Code::InvalidArgument
+                                {
+                                    report.lock().unwrap().replay_rejections += 1;
+                                    // a backfill has no live stream to fall back to
+                                    if self.range.is_some() {
+                                        return Err(backoff::Error::permanent(anyhow::anyhow!(
+                                            "provider rejected from_slot replay: {}",
+                                            status.message()
+                                        )));
+                                    }
+                                    warn!(
+                                        "Provider rejected from_slot replay: {}",
+                                        status.message()
+                                    );
+                                    skip_replay.store(true, Ordering::Relaxed);
+                                }
+                                break;
+                            }
+                        };
+                        metrics::record_stream_message(
+                            &self.provider,
+                            update.update_oneof.as_ref(),
+                        );
+
+                        {
+                            let mut recorder = recorder.lock().unwrap();
+                            if let Some(writer) = recorder.as_mut()
+                                && let Err(e) = writer.write(&update)
+                            {
+                                error!("Failed to record update, recording stopped: {:?}", e);
+                                *recorder = None;
+                            }
+                        }
+
+                        if let Some(UpdateOneof::Block(block)) = update.update_oneof {
+                            subscription.received_data();
+                            let slot = block.slot;
+                            if let Some((first, last)) = self.range {
+                                if slot < first {
+                                    continue;
+                                }
+                                if slot > last {
+                                    // `last` itself was skipped, the range is complete
+                                    self.gaps.lock().unwrap().extend_to(last);
+                                    return Ok(());
+                                }
+                            }
+
+                            self.on_block(block, Some(SystemTime::now()))
+                                .await
+                                .map_err(backoff::Error::permanent)?;
+
+                            if self.range.is_some_and(|(_, last)| slot == last) {
+                                info!("Backfill reached slot {}", slot);
+                                return Ok(());
+                            }
+                        } else if let Some(UpdateOneof::Slot(update)) = update.update_oneof
+                            && self
+                                .range
+                                .is_none_or(|(first, last)| (first..=last).contains(&update.slot))
+                        {
+                            self.on_slot(&update);
+                        }
+                    }
+
+                    Err(backoff::Error::transient(anyhow::anyhow!("Stream ended")))
+                }
+            })
+            .await
+    }
+
+    // Verifies the blocks and slot statuses of a stream recorded with a `Recorder`.
+    pub async fn replay(&self, recording: Recording) -> anyhow::Result<()> {
+        // the file is read on a blocking thread, a queue's worth of updates ahead
+        let (updates, mut updates_rx) = mpsc::channel(self.config.queue_size);
+        tokio::task::spawn_blocking(move || {
+            for update in recording {
+                if updates.blocking_send(update).is_err() {
+                    break;
+                }
+            }
+        });
+
+        while let Some(update) = updates_rx.recv().await {
+            let update = update?;
+            metrics::record_stream_message(&self.provider, update.update_oneof.as_ref());
+            match update.update_oneof {
+                Some(UpdateOneof::Block(block)) => self.on_block(block, None).await?,
+                Some(UpdateOneof::Slot(update)) => self.on_slot(&update),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Queues a block for verification, unless it was already delivered, and runs
+    // the periodic gap check. `received_at` is the block's wall-clock arrival,
+    // None when it is not known. Fails only when the workers are gone.
+    pub async fn on_block(
+        &self,
+        block: SubscribeUpdateBlock,
+        received_at: Option<SystemTime>,
+    ) -> anyhow::Result<()> {
+        let slot = block.slot;
+        // let grpc_tx_count = block.transactions.len() as u64;
+        let grpc_tx_count = block.executed_transaction_count;
+
+        self.slot_statuses.lock().unwrap().delivered(slot);
+        let replaced = self.forks.lock().unwrap().record(slot, &block.blockhash);
+        let is_new = self.gaps.lock().unwrap().record(slot);
+        {
+            let mut rep = self.report.lock().unwrap();
+            if let Some(previous) = &replaced {
+                rep.duplicate_slots += 1;
+                metrics::DUPLICATE_SLOTS
+                    .with_label_values(&[&self.provider])
+                    .inc();
+                warn!(
+                    "DUPLICATE SLOT slot {} → blockhash {} replaced by {}, verifying both",
+                    slot, previous, block.blockhash
+                );
+            } else if !is_new {
+                rep.duplicate_blocks += 1;
+                metrics::DUPLICATE_BLOCKS
+                    .with_label_values(&[&self.provider])
+                    .inc();
+                info!("DUPLICATE slot {} → already verified, skipping", slot);
+                return Ok(());
+            }
+            if rep.in_replay_window(slot) {
+                rep.replayed_blocks += 1;
+            }
+            rep.total_blocks += 1;
+            rep.total_grpc_txs += grpc_tx_count;
+            metrics::BLOCKS_RECEIVED
+                .with_label_values(&[&self.provider])
+                .inc();
+        }
+        if let Some((provider, comparison)) = &self.comparison {
+            comparison
+                .lock()
+                .unwrap()
+                .record(*provider, &block, replaced.is_some());
+        }
+
+        let job = VerifyJob {
+            slot,
+            grpc_count: grpc_tx_count,
+            transactions: block.transactions,
+            blockhash: block.blockhash,
+            enqueued_at: Instant::now(),
+            received_at,
+            block_time: block.block_time.map(|time| time.timestamp),
+        };
+        enqueue(&self.jobs, job, &self.report).await?;
+
+        let check_due = self
+            .gaps
+            .lock()
+            .unwrap()
+            .check_due(self.config.gap_check_interval);
+        if check_due && let Err(e) = check_gaps(&*self.reference, &self.gaps, &self.report).await {
+            error!("RPC gap check error: {:?}", e);
+        }
+        Ok(())
+    }
+
+    // Follows a slot's status on the slot stream subscribed alongside the blocks.
+    pub fn on_slot(&self, update: &SubscribeUpdateSlot) {
+        track_slot_statuses(
+            &self.provider,
+            &self.slot_statuses,
+            &self.report,
+            |tracker, stats| tracker.record(update, stats),
+        );
+    }
+
+    // Waits for the queued verifications, runs the final gap check and returns
+    // the final report.
+    pub async fn finish(self, shutdown: &mut Shutdown) -> Report {
+        // closing the queue lets the workers finish the blocks already received
+        drop(self.jobs);
+        let abandoned = drain_workers(self.workers, shutdown, self.config.shutdown_timeout).await;
+        self.report.lock().unwrap().abandoned_blocks += abandoned;
+
+        if let Err(e) = check_gaps(&*self.reference, &self.gaps, &self.report).await {
+            error!("RPC gap check error: {:?}", e);
+        }
+        track_slot_statuses(
+            &self.provider,
+            &self.slot_statuses,
+            &self.report,
+            |tracker, stats| tracker.finish(stats),
+        );
+
+        std::mem::take(&mut *self.report.lock().unwrap())
+    }
+}
+
+// Runs `track` on the slot status tracker and counts the anomalies it flagged.
+fn track_slot_statuses(
+    provider: &str,
+    tracker: &Mutex<SlotStatusTracker>,
+    report: &Mutex<Report>,
+    track: impl FnOnce(&mut SlotStatusTracker, &mut SlotStatusStats),
+) {
+    let mut rep = report.lock().unwrap();
+    let flagged = rep.slot_status.anomalies.len();
+    track(&mut tracker.lock().unwrap(), &mut rep.slot_status);
+    for anomaly in &rep.slot_status.anomalies[flagged..] {
+        metrics::SLOT_STATUS_ANOMALIES
+            .with_label_values(&[provider, &anomaly.kind.to_string()])
+            .inc();
+    }
+}
+
+pub(crate) async fn compare_with_rpc(
+    client: &impl ReferenceBlockSource,
+    job: &VerifyJob,
+    config: VerifierConfig,
+    report: &Arc<Mutex<Report>>,
+) {
+    let slot = job.slot;
+    let grpc_count = job.grpc_count;
+    let grpc_transactions = &job.transactions;
+    let received_at_ms = job.received_at.map(unix_ms);
+    let block_time_lag_ms = received_at_ms
+        .zip(job.block_time)
+        .map(|(received_at, block_time)| received_at - block_time * 1000);
+
+    let deep_compare = config.deep_compare;
+    let block_config = block_config(deep_compare);
+    // below finalized commitment the delay stage held the block back already
+    let finalized = config.commitment == CommitmentLevel::Finalized;
+
+    let started = Instant::now();
+    let fetched =
+        get_block_with_retry(client, slot, block_config, config.rpc_retry_timeout, report).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let served_at_ms = unix_ms(SystemTime::now());
+
+    let rpc_disagreement = client.take_disagreement(slot);
+    if let Some(disagreement) = &rpc_disagreement {
+        warn!(
+            "RPC DISAGREEMENT slot {} → {}",
+            slot,
+            disagreement
+                .answers
+                .iter()
+                .map(|(endpoint, answer)| format!("{}: {}", endpoint, answer))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    let new_record = |status, rpc_disagreement| {
+        let mut record = SlotRecord::new(slot, status);
+        record.grpc_tx_count = Some(grpc_count);
+        record.latency_ms = Some(latency_ms);
+        record.rpc_disagreement = rpc_disagreement;
+        record.received_at_unix_ms = received_at_ms;
+        record.block_time_lag_ms = block_time_lag_ms;
+        record
+    };
+
+    let block = match fetched {
+        Ok(block) => block,
+        // the block was on a fork that the finalized chain skipped
+        Err((UnverifiedReason::SlotSkipped, e)) if !finalized => {
+            info!("ROLLED BACK slot {} → never finalized: {}", slot, e);
+            let mut record = new_record(BlockStatus::RolledBack, rpc_disagreement);
+            record.error = Some("slot skipped on the finalized chain".to_string());
+            report.lock().unwrap().record_rolled_back(record);
+            return;
+        }
+        Err((reason, e)) => {
+            let reason = match &rpc_disagreement {
+                Some(disagreement) if !disagreement.quorum => UnverifiedReason::NoRpcQuorum,
+                _ => reason,
+            };
+            info!("UNVERIFIED slot {} → {}: {}", slot, reason, e);
+            let mut record = new_record(BlockStatus::Unverified, rpc_disagreement);
+            record.error = Some(e.to_string());
+            record.unverified_reason = Some(reason);
+            report.lock().unwrap().record_unverified(record);
+            return;
+        }
+    };
+
+    // a different block was finalized for the slot
+    if !finalized && block.blockhash != job.blockhash {
+        info!(
+            "ROLLED BACK slot {} → delivered blockhash {}, finalized {}",
+            slot, job.blockhash, block.blockhash
+        );
+        let mut record = new_record(BlockStatus::RolledBack, rpc_disagreement);
+        record.rpc_tx_count = Some(block_signatures(&block).len() as u64);
+        record.error = Some(format!(
+            "finalized blockhash {} differs from delivered {}",
+            block.blockhash, job.blockhash
+        ));
+        report.lock().unwrap().record_rolled_back(record);
+        return;
+    }
+
+    let grpc_signatures: Vec<String> = grpc_transactions
+        .iter()
+        .map(|tx| bs58::encode(&tx.signature).into_string())
+        .collect();
+    let rpc_signatures = block_signatures(&block);
+    let rpc_count = rpc_signatures.len() as u64;
+    let diff = SignatureDiff::compute(&grpc_signatures, &rpc_signatures);
+
+    let meta_diffs = if deep_compare {
+        match compare_transaction_metas(grpc_transactions, &grpc_signatures, &block) {
+            Ok(meta_diffs) => meta_diffs,
+            Err(e) => {
+                let reason = UnverifiedReason::UndecodableMeta;
+                info!("UNVERIFIED slot {} → {}: {:#}", slot, reason, e);
+                let mut record = new_record(BlockStatus::Unverified, rpc_disagreement);
+                record.rpc_tx_count = Some(rpc_count);
+                record.signature_diff = diff;
+                record.error = Some(format!("{:#}", e));
+                record.unverified_reason = Some(reason);
+                report.lock().unwrap().record_unverified(record);
+                return;
+            }
+        }
+    } else {
+        vec![]
+    };
+
+    let status = if grpc_count != rpc_count || !diff.is_empty() || !meta_diffs.is_empty() {
+        info!(
+            "MISMATCH slot {} → gRPC Tx Count={} RPC Tx Count={} Missing={} Extra={} Duplicated={} Meta Diffs={}",
+            slot,
+            grpc_count,
+            rpc_count,
+            diff.missing.len(),
+            diff.extra.len(),
+            diff.duplicated.len(),
+            meta_diffs.len()
+        );
+        BlockStatus::Mismatch
+    } else {
+        info!(
+            "MATCH slot {} → gRPC Tx Count={} RPC Tx Count={}",
+            slot, grpc_count, rpc_count
+        );
+        BlockStatus::Match
+    };
+
+    let mut record = new_record(status, rpc_disagreement);
+    record.rpc_tx_count = Some(rpc_count);
+    record.signature_diff = diff;
+    record.meta_diffs = meta_diffs;
+    // only meaningful for blocks that just arrived, not for a replayed recording;
+    // the time the delay stage held the block back does not count
+    if let Some(received_at_ms) = received_at_ms {
+        let held_ms = if finalized {
+            0
+        } else {
+            config.finalization_delay.as_millis() as i64
+        };
+        let rpc_lag_ms = (served_at_ms - received_at_ms - held_ms).max(0);
+        record.rpc_lag_ms = Some(rpc_lag_ms);
+    }
+    report.lock().unwrap().record_verified(record);
+}
+
+// Compares the status meta of every transaction present on both sides. Transactions
+// missing on either side are already covered by the signature diff.
+fn compare_transaction_metas(
+    grpc_transactions: &[SubscribeUpdateTransactionInfo],
+    grpc_signatures: &[String],
+    block: &UiConfirmedBlock,
+) -> anyhow::Result<Vec<MetaDiff>> {
+    let grpc_metas: HashMap<&str, &SubscribeUpdateTransactionInfo> = grpc_signatures
+        .iter()
+        .map(String::as_str)
+        .zip(grpc_transactions)
+        .collect();
+
+    let mut diffs = vec![];
+    for tx in block.transactions.iter().flatten() {
+        let EncodedTransaction::Json(ui_tx) = &tx.transaction else {
+            continue;
+        };
+        let Some(signature) = ui_tx.signatures.first() else {
+            continue;
+        };
+        let Some(grpc_tx) = grpc_metas.get(signature.as_str()) else {
+            continue;
+        };
+
+        let fields = match (&grpc_tx.meta, &tx.meta) {
+            (Some(grpc_meta), Some(rpc_meta)) => {
+                let grpc_meta = TxMetaSnapshot::from_grpc(grpc_meta)
+                    .with_context(|| format!("gRPC meta of transaction {}", signature))?;
+                let rpc_meta = TxMetaSnapshot::from_rpc(rpc_meta)
+                    .with_context(|| format!("RPC meta of transaction {}", signature))?;
+                grpc_meta.diff(&rpc_meta)
+            }
+            (None, None) => vec![],
+            _ => vec!["meta"],
+        };
+        if !fields.is_empty() {
+            diffs.push(MetaDiff {
+                signature: signature.clone(),
+                fields,
+            });
+        }
+    }
+    Ok(diffs)
+}
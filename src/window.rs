@@ -0,0 +1,164 @@
+use {
+    crate::{
+        latency::Latency,
+        report::{BlockStatus, Report, SlotRecord},
+        slots::SlotAnomaly,
+    },
+    log::{error, info},
+    serde::Serialize,
+    std::{
+        fs::OpenOptions,
+        io::{BufWriter, Write},
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tokio::time::{Instant, interval_at},
+};
+
+// The report counters a daemon window is made of.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct Counters {
+    pub total_blocks: u64,
+    pub verified_blocks: u64,
+    pub unverified_slots: u64,
+    pub mismatched_blocks: u64,
+    pub missing_blocks: u64,
+    pub duplicate_blocks: u64,
+    pub rolled_back_blocks: u64,
+    pub duplicate_slots: u64,
+    pub reconnects: u64,
+    pub stalls: u64,
+    pub rpc_retries: u64,
+    pub rpc_disagreements: u64,
+    pub slot_status_anomalies: u64,
+}
+
+impl Counters {
+    fn from_report(report: &Report) -> Self {
+        Self {
+            total_blocks: report.total_blocks,
+            verified_blocks: report.verified_blocks,
+            unverified_slots: report.unverified_slots,
+            mismatched_blocks: report.mismatched_blocks,
+            missing_blocks: report.missing_blocks,
+            duplicate_blocks: report.duplicate_blocks,
+            rolled_back_blocks: report.rolled_back_blocks,
+            duplicate_slots: report.duplicate_slots,
+            reconnects: report.reconnects,
+            stalls: report.stalls,
+            rpc_retries: report.rpc_retries,
+            rpc_disagreements: report.rpc_disagreements,
+            slot_status_anomalies: report.slot_status.anomaly_count(),
+        }
+    }
+
+    fn since(self, start: Self) -> Self {
+        Self {
+            total_blocks: self.total_blocks - start.total_blocks,
+            verified_blocks: self.verified_blocks - start.verified_blocks,
+            unverified_slots: self.unverified_slots - start.unverified_slots,
+            mismatched_blocks: self.mismatched_blocks - start.mismatched_blocks,
+            missing_blocks: self.missing_blocks - start.missing_blocks,
+            duplicate_blocks: self.duplicate_blocks - start.duplicate_blocks,
+            rolled_back_blocks: self.rolled_back_blocks - start.rolled_back_blocks,
+            duplicate_slots: self.duplicate_slots - start.duplicate_slots,
+            reconnects: self.reconnects - start.reconnects,
+            stalls: self.stalls - start.stalls,
+            rpc_retries: self.rpc_retries - start.rpc_retries,
+            rpc_disagreements: self.rpc_disagreements - start.rpc_disagreements,
+            slot_status_anomalies: self.slot_status_anomalies - start.slot_status_anomalies,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WindowSummary {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub provider: String,
+    pub window: u64,
+    pub ended_at_unix: u64,
+    pub duration_secs: u64,
+    #[serde(flatten)]
+    pub counters: Counters,
+    pub latency: Latency,
+    // the window's mismatched, unverified, rolled back and missing slots, and
+    // matched ones RPC disagreed on
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub slots: Vec<SlotRecord>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub anomalies: Vec<SlotAnomaly>,
+}
+
+// Emits a summary of the last `interval` every `interval`, until aborted.
+// Blocks are counted in the window in which their verification completes. Each
+// window takes the slot records, latency samples and slot status anomalies
+// along, a daemon would otherwise accumulate them forever.
+pub async fn run_windows(interval: Duration, report: Arc<Mutex<Report>>, file: Option<PathBuf>) {
+    let mut ticker = interval_at(Instant::now() + interval, interval);
+    let mut start = Counters::default();
+
+    for window in 1.. {
+        ticker.tick().await;
+        let (provider, counters, latency, mut slots, anomalies) = {
+            let mut rep = report.lock().unwrap();
+            let now = Counters::from_report(&rep);
+            (
+                rep.provider.clone(),
+                now.since(std::mem::replace(&mut start, now)),
+                std::mem::take(&mut rep.latency),
+                std::mem::take(&mut rep.slots),
+                std::mem::take(&mut rep.slot_status.anomalies),
+            )
+        };
+        // matched slots carry nothing worth keeping unless RPC disagreed on them
+        slots.retain(|record| {
+            record.status != BlockStatus::Match || record.rpc_disagreement.is_some()
+        });
+
+        let summary = WindowSummary {
+            provider,
+            window,
+            ended_at_unix: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            duration_secs: interval.as_secs(),
+            counters,
+            latency,
+            slots,
+            anomalies,
+        };
+        info!(
+            "WINDOW #{}{} → {} blocks, {} verified, {} mismatched, {} missing, {} unverified, {} reconnects, {} stalls",
+            window,
+            if summary.provider.is_empty() {
+                String::new()
+            } else {
+                format!(" {}", summary.provider)
+            },
+            counters.total_blocks,
+            counters.verified_blocks,
+            counters.mismatched_blocks,
+            counters.missing_blocks,
+            counters.unverified_slots,
+            counters.reconnects,
+            counters.stalls
+        );
+
+        if let Some(path) = &file
+            && let Err(e) = append_json_line(path, &summary)
+        {
+            error!("Failed to write window summary: {:?}", e);
+        }
+    }
+}
+
+fn append_json_line(path: &Path, summary: &WindowSummary) -> anyhow::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut out = BufWriter::new(file);
+    serde_json::to_writer(&mut out, summary)?;
+    writeln!(out)?;
+    out.flush()?;
+    Ok(())
+}
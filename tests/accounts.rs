@@ -0,0 +1,128 @@
+mod common;
+
+use {
+    base64::{Engine, engine::general_purpose::STANDARD},
+    common::{
+        geyser::{Event, MockGeyser, account, slot},
+        init_logger,
+    },
+    solana_client::{
+        client_error::ClientError,
+        rpc_response::{Response, RpcResponseContext, UiAccount, UiAccountData, UiAccountEncoding},
+    },
+    solana_grpc_integrity_checker::{
+        ReferenceAccountSource,
+        accounts::{AccountStatus, AccountVerifier},
+    },
+    solana_sdk::pubkey::Pubkey,
+    std::{collections::HashMap, time::Duration},
+    yellowstone_grpc_proto::geyser::{SlotStatus, SubscribeRequest},
+};
+
+// Finalized account states, answered base64+zstd encoded like RPC does.
+struct AccountLedger(HashMap<Pubkey, (u64, UiAccountData)>);
+
+impl AccountLedger {
+    fn insert(&mut self, pubkey: [u8; 32], lamports: u64, data: &[u8]) {
+        let data = STANDARD.encode(zstd::encode_all(data, 0).unwrap());
+        self.0.insert(
+            Pubkey::new_from_array(pubkey),
+            (
+                lamports,
+                UiAccountData::Binary(data, UiAccountEncoding::Base64Zstd),
+            ),
+        );
+    }
+}
+
+impl ReferenceAccountSource for AccountLedger {
+    async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+        min_context_slot: u64,
+    ) -> Result<Response<Vec<Option<UiAccount>>>, ClientError> {
+        Ok(Response {
+            context: RpcResponseContext {
+                slot: min_context_slot,
+                api_version: None,
+            },
+            value: pubkeys
+                .iter()
+                .map(|pubkey| {
+                    self.0.get(pubkey).map(|(lamports, data)| UiAccount {
+                        lamports: *lamports,
+                        data: data.clone(),
+                        owner: Pubkey::default().to_string(),
+                        executable: false,
+                        rent_epoch: 0,
+                        space: None,
+                    })
+                })
+                .collect(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn verifies_accounts_at_every_finalized_slot() {
+    init_logger();
+    let geyser = MockGeyser::spawn(vec![vec![
+        account(100, [1; 32], 10, vec![1, 2, 3]),
+        account(100, [2; 32], 20, vec![]),
+        slot(100, SlotStatus::SlotFinalized),
+        account(101, [3; 32], 30, vec![]),
+        slot(101, SlotStatus::SlotFinalized),
+        Event::Hang,
+    ]])
+    .await;
+    let mut ledger = AccountLedger(HashMap::new());
+    ledger.insert([1; 32], 10, &[1, 2, 3]);
+    ledger.insert([2; 32], 21, &[]);
+    // not base64, the account cannot be compared
+    ledger.0.insert(
+        Pubkey::new_from_array([3; 32]),
+        (
+            30,
+            UiAccountData::Binary("!".to_string(), UiAccountEncoding::Base64Zstd),
+        ),
+    );
+
+    let source = geyser.source();
+    let verifier = AccountVerifier::new(ledger, None);
+    let verified = async {
+        while verifier.report.lock().unwrap().total_updates < 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // the last finalized slot follows the last update
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    };
+    tokio::select! {
+        outcome = verifier.stream(&source, SubscribeRequest::default()) => {
+            panic!("stream stopped: {:?}", outcome)
+        }
+        _ = tokio::time::timeout(Duration::from_secs(10), verified) => {}
+    }
+
+    let report = verifier.finish();
+    assert_eq!(report.total_updates, 3);
+    assert_eq!(report.verified_accounts, 1);
+    assert_eq!(report.mismatched_accounts, 1);
+    assert_eq!(report.inconclusive_accounts, 1);
+    assert_eq!(report.accounts.len(), 2);
+    assert_eq!(report.accounts[0].status, AccountStatus::Mismatch);
+    assert_eq!(report.accounts[0].fields, vec!["lamports"]);
+    let undecodable = &report.accounts[1];
+    assert_eq!(undecodable.status, AccountStatus::Inconclusive);
+    assert_eq!(
+        undecodable.pubkey,
+        Pubkey::new_from_array([3; 32]).to_string()
+    );
+    assert!(
+        undecodable
+            .reason
+            .as_deref()
+            .is_some_and(|reason| reason.contains("undecodable")),
+        "{:?}",
+        undecodable.reason
+    );
+}
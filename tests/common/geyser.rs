@@ -0,0 +1,240 @@
+use {
+    super::signature,
+    futures::StreamExt,
+    solana_grpc_integrity_checker::GeyserSource,
+    std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    tokio::{net::TcpListener, sync::mpsc},
+    tokio_stream::wrappers::{ReceiverStream, TcpListenerStream},
+    tonic::{Code, Request, Response, Status, Streaming, transport::Server},
+    yellowstone_grpc_proto::geyser::{
+        GetBlockHeightRequest, GetBlockHeightResponse, GetLatestBlockhashRequest,
+        GetLatestBlockhashResponse, GetSlotRequest, GetSlotResponse, GetVersionRequest,
+        GetVersionResponse, IsBlockhashValidRequest, IsBlockhashValidResponse, PingRequest,
+        PongResponse, SlotStatus, SubscribeReplayInfoRequest, SubscribeReplayInfoResponse,
+        SubscribeRequest, SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+        SubscribeUpdateBlock, SubscribeUpdatePing, SubscribeUpdateSlot,
+        SubscribeUpdateTransactionInfo,
+        geyser_server::{Geyser, GeyserServer},
+        subscribe_update::UpdateOneof,
+    },
+};
+
+// One scripted step of a mock subscription. A subscription ends, like a
+// provider disconnect, once its script is exhausted.
+#[derive(Debug, Clone)]
+pub enum Event {
+    Block(Box<SubscribeUpdateBlock>),
+    Account(Box<SubscribeUpdateAccount>),
+    Slot(SubscribeUpdateSlot),
+    Ping,
+    // fails the subscription with this status
+    Error(Code),
+    Pause(Duration),
+    // keeps the subscription open without sending anything
+    Hang,
+}
+
+// A block with `txs` transactions, signed like `common::signature`.
+pub fn block(slot: u64, txs: u64) -> Event {
+    Event::Block(Box::new(SubscribeUpdateBlock {
+        slot,
+        parent_slot: slot.saturating_sub(1),
+        executed_transaction_count: txs,
+        transactions: (0..txs)
+            .map(|index| SubscribeUpdateTransactionInfo {
+                signature: signature(slot, index),
+                index,
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    }))
+}
+
+// A write of `pubkey` in `slot`, owned by the system program.
+pub fn account(slot: u64, pubkey: [u8; 32], lamports: u64, data: Vec<u8>) -> Event {
+    Event::Account(Box::new(SubscribeUpdateAccount {
+        account: Some(SubscribeUpdateAccountInfo {
+            pubkey: pubkey.to_vec(),
+            lamports,
+            owner: vec![0; 32],
+            executable: false,
+            rent_epoch: 0,
+            data,
+            write_version: slot,
+            txn_signature: None,
+        }),
+        slot,
+        is_startup: false,
+    }))
+}
+
+pub fn slot(slot: u64, status: SlotStatus) -> Event {
+    Event::Slot(SubscribeUpdateSlot {
+        slot,
+        parent: slot.checked_sub(1),
+        status: status as i32,
+        dead_error: None,
+    })
+}
+
+// A Yellowstone-compatible gRPC server on localhost. Every subscription plays
+// the next script; once they run out, subscriptions hang.
+pub struct MockGeyser {
+    pub endpoint: String,
+    requests: Arc<Mutex<Vec<Vec<SubscribeRequest>>>>,
+}
+
+impl MockGeyser {
+    pub async fn spawn(scripts: Vec<Vec<Event>>) -> Self {
+        Self::spawn_with_replay_info(scripts, None).await
+    }
+
+    // `first_available` is the replay horizon reported by `SubscribeReplayInfo`.
+    pub async fn spawn_with_replay_info(
+        scripts: Vec<Vec<Event>>,
+        first_available: Option<u64>,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let service = Service {
+            scripts: Mutex::new(scripts.into()),
+            requests: requests.clone(),
+            first_available,
+        };
+        tokio::spawn(
+            Server::builder()
+                .add_service(GeyserServer::new(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        Self { endpoint, requests }
+    }
+
+    pub fn source(&self) -> GeyserSource {
+        GeyserSource {
+            endpoint: self.endpoint.clone(),
+            x_token: Some("mock".to_string()),
+        }
+    }
+
+    // Every request received so far, one list per subscription.
+    pub fn requests(&self) -> Vec<Vec<SubscribeRequest>> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+struct Service {
+    scripts: Mutex<VecDeque<Vec<Event>>>,
+    requests: Arc<Mutex<Vec<Vec<SubscribeRequest>>>>,
+    first_available: Option<u64>,
+}
+
+#[tonic::async_trait]
+impl Geyser for Service {
+    type SubscribeStream = ReceiverStream<Result<SubscribeUpdate, Status>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<Streaming<SubscribeRequest>>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let subscription = {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(vec![]);
+            requests.len() - 1
+        };
+        let requests = self.requests.clone();
+        let mut incoming = request.into_inner();
+        tokio::spawn(async move {
+            while let Some(Ok(request)) = incoming.next().await {
+                requests.lock().unwrap()[subscription].push(request);
+            }
+        });
+
+        let script = self.scripts.lock().unwrap().pop_front();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            for event in script.unwrap_or_else(|| vec![Event::Hang]) {
+                let update_oneof = match event {
+                    Event::Block(block) => UpdateOneof::Block(*block),
+                    Event::Account(account) => UpdateOneof::Account(*account),
+                    Event::Slot(slot) => UpdateOneof::Slot(slot),
+                    Event::Ping => UpdateOneof::Ping(SubscribeUpdatePing {}),
+                    Event::Error(code) => {
+                        let _ = tx.send(Err(Status::new(code, "scripted error"))).await;
+                        return;
+                    }
+                    Event::Pause(duration) => {
+                        tokio::time::sleep(duration).await;
+                        continue;
+                    }
+                    Event::Hang => {
+                        tx.closed().await;
+                        return;
+                    }
+                };
+                let update = SubscribeUpdate {
+                    filters: vec!["client".to_string()],
+                    update_oneof: Some(update_oneof),
+                    ..Default::default()
+                };
+                if tx.send(Ok(update)).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn subscribe_replay_info(
+        &self,
+        _request: Request<SubscribeReplayInfoRequest>,
+    ) -> Result<Response<SubscribeReplayInfoResponse>, Status> {
+        Ok(Response::new(SubscribeReplayInfoResponse {
+            first_available: self.first_available,
+        }))
+    }
+
+    async fn ping(&self, _request: Request<PingRequest>) -> Result<Response<PongResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn get_latest_blockhash(
+        &self,
+        _request: Request<GetLatestBlockhashRequest>,
+    ) -> Result<Response<GetLatestBlockhashResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn get_block_height(
+        &self,
+        _request: Request<GetBlockHeightRequest>,
+    ) -> Result<Response<GetBlockHeightResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn get_slot(
+        &self,
+        _request: Request<GetSlotRequest>,
+    ) -> Result<Response<GetSlotResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn is_blockhash_valid(
+        &self,
+        _request: Request<IsBlockhashValidRequest>,
+    ) -> Result<Response<IsBlockhashValidResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn get_version(
+        &self,
+        _request: Request<GetVersionRequest>,
+    ) -> Result<Response<GetVersionResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+}
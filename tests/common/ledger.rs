@@ -0,0 +1,76 @@
+use {
+    super::signature,
+    solana_client::{
+        client_error::ClientError, rpc_config::RpcBlockConfig, rpc_response::UiConfirmedBlock,
+    },
+    solana_grpc_integrity_checker::{ReferenceBlockSource, rpc::slot_skipped_error},
+    std::collections::BTreeMap,
+};
+
+// The finalized chain as the reference sees it: every produced slot with the
+// signatures of its transactions. Slots that are absent were skipped.
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    pub blocks: BTreeMap<u64, Vec<String>>,
+}
+
+impl Ledger {
+    // Every slot in `slots` with `txs` transactions, signed like `common::signature`.
+    pub fn with_blocks(slots: impl IntoIterator<Item = u64>, txs: u64) -> Self {
+        let blocks = slots
+            .into_iter()
+            .map(|slot| {
+                let signatures = (0..txs)
+                    .map(|index| bs58::encode(signature(slot, index)).into_string())
+                    .collect();
+                (slot, signatures)
+            })
+            .collect();
+        Self { blocks }
+    }
+
+    pub fn block(&self, slot: u64) -> Option<UiConfirmedBlock> {
+        let signatures = self.blocks.get(&slot)?;
+        Some(UiConfirmedBlock {
+            previous_blockhash: String::new(),
+            blockhash: String::new(),
+            parent_slot: slot.saturating_sub(1),
+            transactions: None,
+            signatures: Some(signatures.clone()),
+            rewards: None,
+            num_reward_partitions: None,
+            block_time: None,
+            block_height: None,
+        })
+    }
+
+    pub fn slots(&self, start: u64, end: u64) -> Vec<u64> {
+        self.blocks
+            .range(start..=end)
+            .map(|(slot, _)| *slot)
+            .collect()
+    }
+
+    pub fn tip(&self) -> u64 {
+        self.blocks.keys().next_back().copied().unwrap_or_default()
+    }
+}
+
+// Signature-only blocks, so it only serves runs without --deep-compare.
+impl ReferenceBlockSource for Ledger {
+    async fn get_block(
+        &self,
+        slot: u64,
+        _config: RpcBlockConfig,
+    ) -> Result<UiConfirmedBlock, ClientError> {
+        self.block(slot).ok_or_else(|| slot_skipped_error(slot))
+    }
+
+    async fn get_blocks(&self, start: u64, end: u64) -> Result<Vec<u64>, ClientError> {
+        Ok(self.slots(start, end))
+    }
+
+    async fn get_slot(&self) -> Result<u64, ClientError> {
+        Ok(self.tip())
+    }
+}
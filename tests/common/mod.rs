@@ -0,0 +1,79 @@
+// Shared by the integration tests, each of which only uses part of it.
+#![allow(dead_code)]
+
+pub mod geyser;
+pub mod ledger;
+pub mod rpc;
+
+use {
+    geyser::MockGeyser,
+    solana_grpc_integrity_checker::{
+        ReferenceBlockSource, Report, Verifier, VerifierConfig,
+        report::{BlockStatus, SlotRecord},
+        shutdown::Shutdown,
+    },
+    std::time::Duration,
+    yellowstone_grpc_proto::geyser::{CommitmentLevel, SubscribeRequest},
+};
+
+const BACKFILL_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn init_logger() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Short timeouts so reconnects and retries stay well within a test run.
+pub fn verifier_config() -> VerifierConfig {
+    VerifierConfig {
+        deep_compare: false,
+        rpc_retry_timeout: Duration::from_secs(2),
+        rpc_workers: 2,
+        queue_size: 8,
+        gap_check_interval: Duration::from_secs(60),
+        stall_timeout: Some(Duration::from_secs(5)),
+        shutdown_timeout: Duration::from_secs(5),
+        commitment: CommitmentLevel::Finalized,
+        finalization_delay: Duration::ZERO,
+    }
+}
+
+// A deterministic 64-byte signature for the `index`th transaction of `slot`.
+pub fn signature(slot: u64, index: u64) -> Vec<u8> {
+    let mut signature = vec![0; 64];
+    signature[..8].copy_from_slice(&slot.to_le_bytes());
+    signature[8..16].copy_from_slice(&index.to_le_bytes());
+    signature
+}
+
+// Streams the `from..=to` backfill from `geyser`, verifies it against
+// `reference` and returns the final report.
+pub async fn backfill(
+    geyser: &MockGeyser,
+    reference: impl ReferenceBlockSource,
+    config: VerifierConfig,
+    (from, to): (u64, u64),
+) -> anyhow::Result<Report> {
+    init_logger();
+    let request = SubscribeRequest {
+        commitment: Some(config.commitment as i32),
+        from_slot: Some(from),
+        ..Default::default()
+    };
+    let verifier = Verifier::new(config, reference, Some((from, to)));
+    let outcome = tokio::time::timeout(
+        BACKFILL_TIMEOUT,
+        verifier.stream(&geyser.source(), request, None),
+    )
+    .await
+    .expect("backfill did not complete");
+    let report = verifier.finish(&mut Shutdown::listen()).await;
+    outcome.map(|()| report)
+}
+
+pub fn record(report: &Report, slot: u64) -> Option<&SlotRecord> {
+    report.slots.iter().find(|record| record.slot == slot)
+}
+
+pub fn status(report: &Report, slot: u64) -> Option<BlockStatus> {
+    record(report, slot).map(|record| record.status)
+}
@@ -0,0 +1,155 @@
+use {
+    super::ledger::Ledger,
+    serde_json::{Value, json},
+    solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::CommitmentConfig},
+    std::{
+        collections::{HashMap, VecDeque},
+        sync::{Arc, Mutex},
+    },
+    tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+    },
+};
+
+// JSON-RPC server error codes returned by getBlock
+pub const BLOCK_NOT_AVAILABLE: i64 = -32004;
+pub const SLOT_SKIPPED: i64 = -32007;
+pub const LONG_TERM_STORAGE_SLOT_SKIPPED: i64 = -32009;
+
+// A JSON-RPC endpoint on localhost serving `getBlock`, `getBlocks` and
+// `getSlot` from a `Ledger`. Slots absent from the ledger answer -32007 like a
+// skipped slot; other errors are scripted per slot with `fail_block`.
+pub struct MockRpc {
+    pub url: String,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    ledger: Ledger,
+    // error codes returned by the next getBlock calls for a slot, in order
+    block_errors: HashMap<u64, VecDeque<i64>>,
+    // methods called so far, in order
+    calls: Vec<String>,
+}
+
+impl MockRpc {
+    pub async fn spawn(ledger: Ledger) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State {
+            ledger,
+            ..Default::default()
+        }));
+
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, server_state.clone()));
+            }
+        });
+        Self { url, state }
+    }
+
+    pub fn client(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.url.clone(), CommitmentConfig::finalized())
+    }
+
+    // Fails the next getBlock calls for `slot` with `codes`, one per call.
+    pub fn fail_block(&self, slot: u64, codes: impl IntoIterator<Item = i64>) {
+        self.state
+            .lock()
+            .unwrap()
+            .block_errors
+            .entry(slot)
+            .or_default()
+            .extend(codes);
+    }
+
+    // Number of calls received for `method`.
+    pub fn calls(&self, method: &str) -> usize {
+        let state = self.state.lock().unwrap();
+        state.calls.iter().filter(|call| *call == method).count()
+    }
+}
+
+// One request per connection, which keeps the HTTP handling to a minimum.
+async fn handle_connection(stream: TcpStream, state: Arc<Mutex<State>>) -> anyhow::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse()?;
+        }
+    }
+    let mut body = vec![0; content_length];
+    stream.read_exact(&mut body).await?;
+
+    let request: Value = serde_json::from_slice(&body)?;
+    let response = match handle_request(&request, &mut state.lock().unwrap()) {
+        Ok(result) => json!({"jsonrpc": "2.0", "result": result, "id": request["id"]}),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "error": {"code": code, "message": message},
+            "id": request["id"],
+        }),
+    };
+
+    let body = serde_json::to_vec(&response)?;
+    let header = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    let stream = stream.get_mut();
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+fn handle_request(request: &Value, state: &mut State) -> Result<Value, (i64, String)> {
+    let method = request["method"].as_str().unwrap_or_default();
+    let params = &request["params"];
+    state.calls.push(method.to_string());
+
+    match method {
+        "getBlock" => {
+            let slot = params[0].as_u64().unwrap_or_default();
+            if let Some(code) = state
+                .block_errors
+                .get_mut(&slot)
+                .and_then(VecDeque::pop_front)
+            {
+                return Err((code, format!("scripted error for slot {}", slot)));
+            }
+            match state.ledger.block(slot) {
+                Some(block) => Ok(serde_json::to_value(block).unwrap()),
+                None => Err((
+                    SLOT_SKIPPED,
+                    format!(
+                        "Slot {} was skipped, or missing due to ledger jump to recent snapshot",
+                        slot
+                    ),
+                )),
+            }
+        }
+        "getBlocks" => {
+            let start = params[0].as_u64().unwrap_or_default();
+            let end = params[1].as_u64().unwrap_or(u64::MAX);
+            Ok(json!(state.ledger.slots(start, end)))
+        }
+        "getSlot" => Ok(json!(state.ledger.tip())),
+        _ => Err((-32601, "Method not found".to_string())),
+    }
+}
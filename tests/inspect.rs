@@ -0,0 +1,48 @@
+mod common;
+
+use {
+    common::{
+        geyser::{Event, MockGeyser, block},
+        ledger::Ledger,
+    },
+    solana_grpc_integrity_checker::inspect::inspect_slot,
+    std::time::Duration,
+    yellowstone_grpc_proto::geyser::SubscribeRequest,
+};
+
+#[tokio::test]
+async fn answers_pings_while_replaying_to_the_slot() {
+    let geyser = MockGeyser::spawn(vec![vec![Event::Ping, block(99, 1), block(100, 2)]]).await;
+    let ledger = Ledger::with_blocks(99..=100, 2);
+
+    let inspection = inspect_slot(
+        &geyser.source(),
+        SubscribeRequest::default(),
+        &ledger,
+        100,
+        Duration::from_secs(5),
+        Duration::from_secs(1),
+    )
+    .await
+    .unwrap();
+    let mut out = vec![];
+    inspection.write_text(&mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    // the gRPC column holds the replayed block
+    assert!(text.lines().any(|line| {
+        line.starts_with("Transactions") && line.split_whitespace().nth(1) == Some("2")
+    }));
+
+    let answered = async {
+        while !geyser.requests()[0]
+            .iter()
+            .any(|request| request.ping.is_some())
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), answered)
+        .await
+        .expect("ping was not answered");
+    assert_eq!(geyser.requests()[0][0].from_slot, Some(100));
+}
@@ -0,0 +1,23 @@
+use solana_grpc_integrity_checker::latency::LatencySamples;
+
+#[test]
+fn summarizes_samples_as_nearest_rank_percentiles() {
+    let mut samples = LatencySamples::default();
+    assert!(samples.summary().is_none());
+
+    for ms in (1..=100).rev() {
+        samples.record(ms);
+    }
+    let summary = samples.summary().unwrap();
+    assert_eq!(summary.count, 100);
+    assert_eq!(summary.min_ms, 1);
+    assert_eq!(summary.p50_ms, 50);
+    assert_eq!(summary.p90_ms, 90);
+    assert_eq!(summary.p99_ms, 99);
+    assert_eq!(summary.max_ms, 100);
+
+    let mut single = LatencySamples::default();
+    single.record(-400);
+    let summary = single.summary().unwrap();
+    assert_eq!((summary.p50_ms, summary.p99_ms), (-400, -400));
+}
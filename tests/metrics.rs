@@ -0,0 +1,40 @@
+use {
+    solana_grpc_integrity_checker::metrics,
+    std::net::TcpListener,
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    },
+};
+
+#[tokio::test]
+async fn serves_every_metric_before_first_use() {
+    // reserve a free port, the exporter binds it again
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    metrics::spawn_server(addr).await.unwrap();
+    metrics::init_provider("https://provider.example");
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    for metric in [
+        "grpc_integrity_blocks_received_total{provider=\"https://provider.example\"} 0",
+        "grpc_integrity_mismatched_blocks_total{provider=\"https://provider.example\"} 0",
+        "grpc_integrity_stalls_total{provider=\"https://provider.example\"} 0",
+        "grpc_integrity_stream_messages_total{kind=\"block\",provider=\"https://provider.example\"} 0",
+        "grpc_integrity_slot_status_anomalies_total{kind=\"undelivered\",provider=\"https://provider.example\"} 0",
+        "grpc_integrity_grpc_to_rpc_lag_seconds_count{provider=\"https://provider.example\"} 0",
+        "grpc_integrity_verification_queue_depth{provider=\"https://provider.example\"} 0",
+    ] {
+        assert!(response.contains(metric), "{} not served", metric);
+    }
+}
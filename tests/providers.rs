@@ -0,0 +1,139 @@
+mod common;
+
+use {
+    common::{
+        geyser::{MockGeyser, block},
+        init_logger,
+        ledger::Ledger,
+        rpc::{BLOCK_NOT_AVAILABLE, MockRpc},
+        verifier_config,
+    },
+    futures::future::join_all,
+    solana_grpc_integrity_checker::{
+        Verifier, providers::ProviderComparison, reference::SharedReference, shutdown::Shutdown,
+    },
+    std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    yellowstone_grpc_proto::geyser::{SubscribeRequest, SubscribeUpdateBlock},
+};
+
+#[tokio::test]
+async fn compares_providers_slot_by_slot() {
+    init_logger();
+    let geysers = [
+        MockGeyser::spawn(vec![vec![block(100, 1), block(101, 1), block(102, 1)]]).await,
+        // delivers a different block for 101
+        MockGeyser::spawn(vec![vec![block(100, 1), block(101, 2), block(102, 1)]]).await,
+        // never delivers 102, the backfill ends on 103
+        MockGeyser::spawn(vec![vec![block(100, 1), block(101, 1), block(103, 1)]]).await,
+    ];
+    let ledger = Ledger::with_blocks(100..=102, 1);
+
+    let comparison = Arc::new(Mutex::new(ProviderComparison::new(geysers.len())));
+    let verifiers: Vec<_> = (0..geysers.len())
+        .map(|provider| {
+            Verifier::new(verifier_config(), ledger.clone(), Some((100, 102)))
+                .compare_as(provider, comparison.clone())
+        })
+        .collect();
+    let request = SubscribeRequest {
+        from_slot: Some(100),
+        ..Default::default()
+    };
+    let sources: Vec<_> = geysers.iter().map(MockGeyser::source).collect();
+    let streams = join_all(
+        verifiers
+            .iter()
+            .zip(&sources)
+            .map(|(verifier, source)| verifier.stream(source, request.clone(), None)),
+    );
+    let outcomes = tokio::time::timeout(Duration::from_secs(30), streams)
+        .await
+        .expect("backfills did not complete");
+    assert!(outcomes.iter().all(Result::is_ok));
+
+    let mut reports = vec![];
+    for verifier in verifiers {
+        reports.push(verifier.finish(&mut Shutdown::listen()).await);
+    }
+    let stats = comparison.lock().unwrap().finish();
+
+    assert_eq!(stats[0].delivered, 3);
+    assert_eq!(stats[0].absent, 0);
+    assert_eq!(stats[0].disagreements, 0);
+    assert_eq!(stats[1].delivered, 3);
+    assert_eq!(stats[1].disagreements, 1);
+    assert_eq!(stats[2].delivered, 2);
+    assert_eq!(stats[2].absent, 1);
+    assert_eq!(stats[2].disagreements, 0);
+    assert_eq!(stats.iter().map(|stats| stats.first).sum::<u64>(), 3);
+    assert_eq!(stats[2].lag.summary().unwrap().count, 2);
+
+    assert_eq!(reports[1].mismatched_blocks, 1);
+    assert_eq!(reports[2].missing_blocks, 1);
+}
+
+#[tokio::test]
+async fn providers_share_one_fetch_per_slot() {
+    init_logger();
+    let geysers = [
+        MockGeyser::spawn(vec![vec![block(100, 1), block(101, 1), block(102, 1)]]).await,
+        MockGeyser::spawn(vec![vec![block(100, 1), block(101, 1), block(102, 1)]]).await,
+    ];
+    let rpc = MockRpc::spawn(Ledger::with_blocks(100..=102, 1)).await;
+    // a failed fetch is not shared, the next attempt asks again
+    rpc.fail_block(101, [BLOCK_NOT_AVAILABLE]);
+    let reference = Arc::new(SharedReference::new(rpc.client(), geysers.len()));
+
+    let comparison = Arc::new(Mutex::new(ProviderComparison::new(geysers.len())));
+    let verifiers: Vec<_> = (0..geysers.len())
+        .map(|provider| {
+            Verifier::new(verifier_config(), reference.clone(), Some((100, 102)))
+                .compare_as(provider, comparison.clone())
+        })
+        .collect();
+    let request = SubscribeRequest {
+        from_slot: Some(100),
+        ..Default::default()
+    };
+    let sources: Vec<_> = geysers.iter().map(MockGeyser::source).collect();
+    let streams = join_all(
+        verifiers
+            .iter()
+            .zip(&sources)
+            .map(|(verifier, source)| verifier.stream(source, request.clone(), None)),
+    );
+    let outcomes = tokio::time::timeout(Duration::from_secs(30), streams)
+        .await
+        .expect("backfills did not complete");
+    assert!(outcomes.iter().all(Result::is_ok));
+
+    for verifier in verifiers {
+        let report = verifier.finish(&mut Shutdown::listen()).await;
+        assert_eq!(report.verified_blocks, 3);
+    }
+    assert_eq!(rpc.calls("getBlock"), 4);
+}
+
+#[test]
+fn fork_replacements_leave_compared_slots_alone() {
+    let block = |blockhash: &str| SubscribeUpdateBlock {
+        slot: 100,
+        blockhash: blockhash.to_owned(),
+        ..Default::default()
+    };
+    let mut comparison = ProviderComparison::new(2);
+    comparison.record(0, &block("a"), false);
+    comparison.record(1, &block("a"), false);
+    // the fork replacement arrives once the slot was compared
+    comparison.record(0, &block("b"), true);
+    let stats = comparison.finish();
+
+    assert_eq!(stats[0].delivered, 1);
+    assert_eq!(stats[0].replaced, 1);
+    assert_eq!(stats[0].late, 0);
+    assert_eq!(stats[1].delivered, 1);
+    assert_eq!(stats[1].absent, 0);
+}
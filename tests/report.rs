@@ -0,0 +1,67 @@
+use {
+    solana_grpc_integrity_checker::{
+        Report,
+        report::{BlockStatus, RenderReport, SlotRecord},
+    },
+    std::time::Duration,
+};
+
+fn small_report() -> Report {
+    let mut report = Report::default();
+    report.backpressure.enqueue_wait_total = Duration::from_millis(1500);
+    report.backpressure.queue_wait_max = Duration::from_millis(250);
+
+    let mut mismatch = SlotRecord::new(100, BlockStatus::Mismatch);
+    mismatch.grpc_tx_count = Some(2);
+    mismatch.rpc_tx_count = Some(3);
+    mismatch.latency_ms = Some(40);
+    mismatch.signature_diff.missing = vec!["sig-a".to_string()];
+    mismatch.rpc_lag_ms = Some(120);
+    report.record_verified(mismatch);
+    report.record_missing(101);
+    report
+}
+
+#[test]
+fn renders_csv_rows_per_slot() {
+    let mut out = vec![];
+    small_report().write_csv(&mut out).unwrap();
+    let csv = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(
+        lines,
+        [
+            "slot,status,grpc_tx_count,rpc_tx_count,latency_ms,error,missing_signatures,\
+             extra_signatures,duplicated_signatures,meta_diffs,rpc_disagreement,\
+             received_at_unix_ms,block_time_lag_ms,rpc_lag_ms",
+            "100,mismatch,2,3,40,,1,0,0,0,false,,,120",
+            "101,missing,,,,,0,0,0,0,false,,,",
+        ]
+    );
+}
+
+#[test]
+fn renders_json_with_renamed_fields() {
+    let json = serde_json::to_value(small_report()).unwrap();
+
+    assert_eq!(json["verified_blocks"], 1);
+    assert_eq!(json["mismatched_blocks"], 1);
+    assert_eq!(json["missing_blocks"], 1);
+    assert!(json.get("provider").is_none());
+    let backpressure = &json["backpressure"];
+    assert_eq!(backpressure["enqueue_wait_total_ms"], 1500);
+    assert_eq!(backpressure["enqueue_wait_max_ms"], 0);
+    assert_eq!(backpressure["queue_wait_max_ms"], 250);
+    assert!(backpressure.get("enqueue_wait_total").is_none());
+
+    let slots = json["slots"].as_array().unwrap();
+    assert_eq!(slots[0]["slot"], 100);
+    assert_eq!(slots[0]["status"], "mismatch");
+    assert_eq!(slots[0]["signature_diff"]["missing"][0], "sig-a");
+    assert_eq!(slots[0]["rpc_lag_ms"], 120);
+    assert_eq!(slots[1]["status"], "missing");
+    // unset optional fields are left out
+    assert!(slots[1].get("rpc_lag_ms").is_none());
+    assert!(slots[1].get("signature_diff").is_none());
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use {
+    common::{
+        backfill,
+        geyser::{MockGeyser, block},
+        ledger::Ledger,
+        record,
+        rpc::{BLOCK_NOT_AVAILABLE, LONG_TERM_STORAGE_SLOT_SKIPPED, MockRpc},
+        status, verifier_config,
+    },
+    solana_grpc_integrity_checker::{
+        VerifierConfig, quorum::RpcQuorum, reference::snapshot_rpc, report::BlockStatus,
+        rpc::UnverifiedReason, source::ReferenceBlockSource,
+    },
+    std::{fs, iter, time::Duration},
+};
+
+#[tokio::test]
+async fn verifies_blocks_served_over_json_rpc() {
+    let geyser = MockGeyser::spawn(vec![vec![block(100, 2), block(101, 2)]]).await;
+    let rpc = MockRpc::spawn(Ledger::with_blocks(100..=101, 2)).await;
+
+    let report = backfill(&geyser, rpc.client(), verifier_config(), (100, 101))
+        .await
+        .unwrap();
+    assert_eq!(report.verified_blocks, 2);
+    assert_eq!(report.mismatched_blocks, 0);
+    assert_eq!(report.total_rpc_txs, 4);
+    assert_eq!(report.rpc_retries, 0);
+    assert_eq!(rpc.calls("getBlock"), 2);
+    assert!(rpc.calls("getBlocks") >= 1);
+}
+
+#[tokio::test]
+async fn retries_blocks_that_are_not_available_yet() {
+    let geyser = MockGeyser::spawn(vec![vec![block(100, 1), block(101, 1)]]).await;
+    let rpc = MockRpc::spawn(Ledger::with_blocks(100..=101, 1)).await;
+    rpc.fail_block(101, [BLOCK_NOT_AVAILABLE, BLOCK_NOT_AVAILABLE]);
+
+    let report = backfill(&geyser, rpc.client(), verifier_config(), (100, 101))
+        .await
+        .unwrap();
+    assert_eq!(report.verified_blocks, 2);
+    assert_eq!(report.unverified_slots, 0);
+    assert_eq!(report.rpc_retries, 2);
+    assert_eq!(status(&report, 101), Some(BlockStatus::Match));
+    assert_eq!(rpc.calls("getBlock"), 4);
+}
+
+#[tokio::test]
+async fn leaves_skipped_slots_unverified_without_retrying() {
+    // gRPC delivers 101 although RPC considers it skipped
+    let geyser = MockGeyser::spawn(vec![vec![block(100, 1), block(101, 1), block(102, 1)]]).await;
+    let rpc = MockRpc::spawn(Ledger::with_blocks([100, 102], 1)).await;
+    rpc.fail_block(102, [LONG_TERM_STORAGE_SLOT_SKIPPED]);
+
+    let report = backfill(&geyser, rpc.client(), verifier_config(), (100, 102))
+        .await
+        .unwrap();
+    assert_eq!(report.verified_blocks, 1);
+    assert_eq!(report.unverified_slots, 2);
+    assert_eq!(report.rpc_retries, 0);
+    for slot in [101, 102] {
+        let slot_record = record(&report, slot).unwrap();
+        assert_eq!(slot_record.status, BlockStatus::Unverified);
+        assert_eq!(
+            slot_record.unverified_reason,
+            Some(UnverifiedReason::SlotSkipped)
+        );
+    }
+    assert_eq!(rpc.calls("getBlock"), 3);
+}
+
+#[tokio::test]
+async fn gives_up_once_the_retry_timeout_is_exhausted() {
+    let geyser = MockGeyser::spawn(vec![vec![block(100, 1)]]).await;
+    let rpc = MockRpc::spawn(Ledger::with_blocks([100], 1)).await;
+    rpc.fail_block(100, iter::repeat_n(BLOCK_NOT_AVAILABLE, 1000));
+    let config = VerifierConfig {
+        rpc_retry_timeout: Duration::from_secs(1),
+        ..verifier_config()
+    };
+
+    let report = backfill(&geyser, rpc.client(), config, (100, 100))
+        .await
+        .unwrap();
+    assert_eq!(report.verified_blocks, 0);
+    assert_eq!(report.unverified_slots, 1);
+    assert!(report.rpc_retries >= 1);
+    assert_eq!(
+        record(&report, 100).unwrap().unverified_reason,
+        Some(UnverifiedReason::RetriesExhausted)
+    );
+}
+
+#[tokio::test]
+async fn reconciles_gaps_with_get_blocks() {
+    let geyser = MockGeyser::spawn(vec![vec![block(100, 1), block(103, 1)]]).await;
+    let rpc = MockRpc::spawn(Ledger::with_blocks([100, 102, 103], 1)).await;
+
+    let report = backfill(&geyser, rpc.client(), verifier_config(), (100, 103))
+        .await
+        .unwrap();
+    assert_eq!(report.verified_blocks, 2);
+    assert_eq!(report.missing_blocks, 1);
+    assert_eq!(status(&report, 102), Some(BlockStatus::Missing));
+}
+
+// A ledger whose slot 101 holds a transaction the others never saw.
+fn forked_ledger() -> Ledger {
+    let mut ledger = Ledger::with_blocks(100..=101, 1);
+    ledger
+        .blocks
+        .insert(101, vec!["only-on-this-rpc".to_string()]);
+    ledger
+}
+
+#[tokio::test]
+async fn takes_the_block_a_majority_of_rpcs_agree_on() {
+    let geyser = MockGeyser::spawn(vec![vec![block(100, 1), block(101, 1)]]).await;
+    let rpcs = [
+        MockRpc::spawn(Ledger::with_blocks(100..=101, 1)).await,
+        MockRpc::spawn(forked_ledger()).await,
+        MockRpc::spawn(Ledger::with_blocks(100..=101, 1)).await,
+    ];
+    let quorum = RpcQuorum::new(&rpcs.map(|rpc| rpc.url));
+
+    let report = backfill(&geyser, quorum, verifier_config(), (100, 101))
+        .await
+        .unwrap();
+    assert_eq!(report.verified_blocks, 2);
+    assert_eq!(report.mismatched_blocks, 0);
+    assert_eq!(report.rpc_disagreements, 1);
+    let disagreement = record(&report, 101)
+        .unwrap()
+        .rpc_disagreement
+        .clone()
+        .unwrap();
+    assert!(disagreement.quorum);
+    assert_eq!(disagreement.answers.len(), 3);
+    assert!(record(&report, 100).unwrap().rpc_disagreement.is_none());
+}
+
+#[tokio::test]
+async fn leaves_slots_unverified_without_rpc_quorum() {
+    let geyser = MockGeyser::spawn(vec![vec![block(100, 1), block(101, 1)]]).await;
+    let rpcs = [
+        MockRpc::spawn(Ledger::with_blocks(100..=101, 1)).await,
+        MockRpc::spawn(forked_ledger()).await,
+        // considers 101 skipped
+        MockRpc::spawn(Ledger::with_blocks([100], 1)).await,
+    ];
+    let quorum = RpcQuorum::new(&rpcs.map(|rpc| rpc.url));
+
+    let report = backfill(&geyser, quorum, verifier_config(), (100, 101))
+        .await
+        .unwrap();
+    assert_eq!(report.verified_blocks, 1);
+    assert_eq!(report.unverified_slots, 1);
+    assert_eq!(report.rpc_disagreements, 1);
+    let slot_record = record(&report, 101).unwrap();
+    assert_eq!(
+        slot_record.unverified_reason,
+        Some(UnverifiedReason::NoRpcQuorum)
+    );
+    assert!(!slot_record.rpc_disagreement.as_ref().unwrap().quorum);
+}
+
+#[tokio::test]
+async fn snapshots_leave_no_rpc_disagreement_behind() {
+    let rpcs = [
+        MockRpc::spawn(Ledger::with_blocks(100..=101, 1)).await,
+        MockRpc::spawn(forked_ledger()).await,
+        MockRpc::spawn(Ledger::with_blocks(100..=101, 1)).await,
+    ];
+    let quorum = RpcQuorum::new(&rpcs.map(|rpc| rpc.url));
+    let out = std::env::temp_dir().join(format!("quorum-snapshot-{}", std::process::id()));
+
+    snapshot_rpc(&quorum, Duration::from_secs(1), 2, 100, 101, &out)
+        .await
+        .unwrap();
+    fs::remove_dir_all(&out).unwrap();
+    assert!(quorum.take_disagreement(101).is_none());
+}
@@ -0,0 +1,102 @@
+use {
+    solana_grpc_integrity_checker::slots::{SlotAnomalyKind, SlotStatusStats, SlotStatusTracker},
+    yellowstone_grpc_proto::geyser::{SlotStatus, SubscribeUpdateSlot},
+};
+
+fn update(slot: u64, status: SlotStatus) -> SubscribeUpdateSlot {
+    SubscribeUpdateSlot {
+        slot,
+        parent: slot.checked_sub(1),
+        status: status as i32,
+        dead_error: None,
+    }
+}
+
+fn anomalies(stats: &SlotStatusStats) -> Vec<(u64, SlotAnomalyKind, &str)> {
+    stats
+        .anomalies
+        .iter()
+        .map(|anomaly| (anomaly.slot, anomaly.kind, anomaly.detail.as_str()))
+        .collect()
+}
+
+#[test]
+fn flags_skipped_and_regressed_statuses() {
+    use SlotStatus::*;
+    let mut tracker = SlotStatusTracker::default();
+    let mut stats = SlotStatusStats::default();
+    let updates = [
+        // in flight when the stream started, not judged
+        (99, SlotFinalized),
+        (100, SlotFirstShredReceived),
+        (100, SlotProcessed),
+        (100, SlotCompleted),
+        (100, SlotConfirmed),
+        (100, SlotFinalized),
+        (101, SlotProcessed),
+        (101, SlotFinalized),
+        (102, SlotCreatedBank),
+        (102, SlotConfirmed),
+        (102, SlotProcessed),
+        (103, SlotProcessed),
+        (103, SlotDead),
+        (104, SlotProcessed),
+        (104, SlotConfirmed),
+        (104, SlotDead),
+    ];
+    for (slot, status) in updates {
+        tracker.record(&update(slot, status), &mut stats);
+    }
+    tracker.finish(&mut stats);
+
+    assert_eq!(stats.updates, 16);
+    assert_eq!(stats.finalized_slots, 3);
+    assert_eq!(stats.dead_slots, 2);
+    assert_eq!(
+        anomalies(&stats),
+        vec![
+            (
+                101,
+                SlotAnomalyKind::SkippedStatus,
+                "finalized without confirmed"
+            ),
+            (
+                102,
+                SlotAnomalyKind::SkippedStatus,
+                "confirmed without processed"
+            ),
+            (
+                102,
+                SlotAnomalyKind::Regression,
+                "processed after confirmed"
+            ),
+            (104, SlotAnomalyKind::Regression, "dead after confirmed"),
+        ]
+    );
+}
+
+#[test]
+fn flags_finalized_slots_the_block_stream_went_past() {
+    use SlotStatus::*;
+    let mut tracker = SlotStatusTracker::default();
+    let mut stats = SlotStatusStats::default();
+    for slot in 100..=103 {
+        for status in [SlotProcessed, SlotConfirmed, SlotFinalized] {
+            tracker.record(&update(slot, status), &mut stats);
+        }
+    }
+    tracker.delivered(100);
+    tracker.delivered(102);
+    tracker.finish(&mut stats);
+
+    // 103 may still be on its way
+    assert_eq!(
+        anomalies(&stats),
+        vec![(
+            101,
+            SlotAnomalyKind::Undelivered,
+            "finalized on the slot stream but never delivered on the block stream"
+        )]
+    );
+    assert_eq!(stats.count(SlotAnomalyKind::Undelivered), 1);
+}
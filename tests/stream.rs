@@ -0,0 +1,552 @@
+mod common;
+
+use {
+    common::{
+        backfill,
+        geyser::{Event, MockGeyser, block, slot},
+        init_logger,
+        ledger::Ledger,
+        record, status, verifier_config,
+    },
+    solana_grpc_integrity_checker::{
+        Verifier, VerifierConfig,
+        geyser_reference::GeyserReference,
+        record::{Recorder, Recording},
+        reference::Fixtures,
+        report::BlockStatus,
+        rpc::UnverifiedReason,
+        shutdown::Shutdown,
+        slots::SlotAnomalyKind,
+    },
+    std::{
+        fs,
+        path::Path,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tonic::Code,
+    yellowstone_grpc_proto::{
+        geyser::{CommitmentLevel, SlotStatus, SubscribeRequest},
+        solana::storage::confirmed_block::{
+            InnerInstruction, InnerInstructions, ReturnData, TokenBalance, TransactionError,
+            TransactionStatusMeta, UiTokenAmount, UnixTimestamp,
+        },
+    },
+};
+
+#[tokio::test]
+async fn verifies_every_block_and_answers_pings() {
+    let geyser = MockGeyser::spawn(vec![vec![
+        Event::Ping,
+        block(100, 3),
+        block(101, 3),
+        block(102, 3),
+    ]])
+    .await;
+    let ledger = Ledger::with_blocks(100..=102, 3);
+
+    let report = backfill(&geyser, ledger, verifier_config(), (100, 102))
+        .await
+        .unwrap();
+    assert_eq!(report.total_blocks, 3);
+    assert_eq!(report.verified_blocks, 3);
+    assert_eq!(report.mismatched_blocks, 0);
+    assert_eq!(report.missing_blocks, 0);
+    assert_eq!(report.total_grpc_txs, 9);
+    assert_eq!(report.total_rpc_txs, 9);
+    assert_eq!(report.reconnects, 0);
+
+    // the ping reply travels on its own, it may land after the last block
+    let answered = async {
+        while !geyser.requests()[0]
+            .iter()
+            .any(|request| request.ping.is_some())
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), answered)
+        .await
+        .expect("ping was not answered");
+    assert_eq!(geyser.requests()[0][0].from_slot, Some(100));
+}
+
+#[tokio::test]
+async fn flags_blocks_that_differ_from_rpc() {
+    let geyser = MockGeyser::spawn(vec![vec![block(100, 3), block(101, 3)]]).await;
+    let mut ledger = Ledger::with_blocks(100..=101, 3);
+    ledger
+        .blocks
+        .get_mut(&101)
+        .unwrap()
+        .push("only-in-rpc".to_string());
+
+    let report = backfill(&geyser, ledger, verifier_config(), (100, 101))
+        .await
+        .unwrap();
+    assert_eq!(report.verified_blocks, 2);
+    assert_eq!(report.mismatched_blocks, 1);
+    assert_eq!(status(&report, 100), Some(BlockStatus::Match));
+    assert_eq!(status(&report, 101), Some(BlockStatus::Mismatch));
+    assert_eq!(
+        record(&report, 101).unwrap().signature_diff.missing,
+        vec!["only-in-rpc".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn resumes_from_last_slot_after_disconnect() {
+    let geyser = MockGeyser::spawn(vec![
+        vec![block(100, 1), block(101, 1)],
+        // the provider replays one block too many
+        vec![block(101, 1), block(102, 1)],
+    ])
+    .await;
+    let ledger = Ledger::with_blocks(100..=102, 1);
+
+    let report = backfill(&geyser, ledger, verifier_config(), (100, 102))
+        .await
+        .unwrap();
+    assert_eq!(report.reconnects, 1);
+    assert_eq!(report.duplicate_blocks, 1);
+    assert_eq!(report.total_blocks, 3);
+    assert_eq!(report.verified_blocks, 3);
+    assert_eq!(report.missing_blocks, 0);
+
+    let requests = geyser.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1][0].from_slot, Some(102));
+}
+
+#[tokio::test]
+async fn reports_undelivered_blocks_as_missing() {
+    // 101 was skipped by the chain, 103 produced but never delivered
+    let geyser = MockGeyser::spawn(vec![vec![block(100, 1), block(102, 1), block(104, 1)]]).await;
+    let ledger = Ledger::with_blocks([100, 102, 103, 104], 1);
+
+    let report = backfill(&geyser, ledger, verifier_config(), (100, 104))
+        .await
+        .unwrap();
+    assert_eq!(report.total_blocks, 3);
+    assert_eq!(report.missing_blocks, 1);
+    assert_eq!(status(&report, 103), Some(BlockStatus::Missing));
+    assert_eq!(status(&report, 101), None);
+}
+
+#[tokio::test]
+async fn reconnects_when_the_stream_stalls() {
+    let geyser =
+        MockGeyser::spawn(vec![vec![block(100, 1), Event::Hang], vec![block(101, 1)]]).await;
+    let ledger = Ledger::with_blocks(100..=101, 1);
+    let config = VerifierConfig {
+        stall_timeout: Some(Duration::from_millis(300)),
+        ..verifier_config()
+    };
+
+    let report = backfill(&geyser, ledger, config, (100, 101)).await.unwrap();
+    assert_eq!(report.stalls, 1);
+    assert_eq!(report.reconnects, 1);
+    assert_eq!(report.verified_blocks, 2);
+}
+
+#[tokio::test]
+async fn pings_do_not_hold_off_the_stall_watchdog() {
+    // answered pings for well past the stall timeout, but no block
+    let mut pinging = vec![block(100, 1)];
+    for _ in 0..20 {
+        pinging.extend([Event::Pause(Duration::from_millis(100)), Event::Ping]);
+    }
+    let geyser = MockGeyser::spawn(vec![pinging, vec![block(101, 1)]]).await;
+    let ledger = Ledger::with_blocks(100..=101, 1);
+    let config = VerifierConfig {
+        stall_timeout: Some(Duration::from_millis(300)),
+        ..verifier_config()
+    };
+
+    let report = backfill(&geyser, ledger, config, (100, 101)).await.unwrap();
+    assert_eq!(report.stalls, 1);
+    assert_eq!(report.reconnects, 1);
+    assert_eq!(report.verified_blocks, 2);
+}
+
+#[tokio::test]
+async fn retries_after_stream_errors() {
+    let geyser = MockGeyser::spawn(vec![
+        vec![block(100, 1), Event::Error(Code::Unavailable)],
+        vec![block(101, 1)],
+    ])
+    .await;
+    let ledger = Ledger::with_blocks(100..=101, 1);
+
+    let report = backfill(&geyser, ledger, verifier_config(), (100, 101))
+        .await
+        .unwrap();
+    assert_eq!(report.reconnects, 1);
+    assert_eq!(report.replay_rejections, 0);
+    assert_eq!(report.verified_blocks, 2);
+}
+
+#[tokio::test]
+async fn backfill_fails_when_replay_is_rejected() {
+    let geyser = MockGeyser::spawn(vec![vec![Event::Error(Code::InvalidArgument)]]).await;
+    let ledger = Ledger::with_blocks(100..=101, 1);
+
+    let error = backfill(&geyser, ledger, verifier_config(), (100, 101))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("rejected from_slot replay"));
+    assert_eq!(geyser.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn measures_arrival_latency() {
+    let Event::Block(mut timed) = block(100, 1) else {
+        unreachable!()
+    };
+    let block_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+        - 10;
+    timed.block_time = Some(UnixTimestamp {
+        timestamp: block_time,
+    });
+    let geyser = MockGeyser::spawn(vec![vec![Event::Block(timed), block(101, 1)]]).await;
+    let ledger = Ledger::with_blocks(100..=101, 1);
+
+    let report = backfill(&geyser, ledger, verifier_config(), (100, 101))
+        .await
+        .unwrap();
+    let slot_record = record(&report, 100).unwrap();
+    let lag = slot_record.block_time_lag_ms.unwrap();
+    assert!((10_000..15_000).contains(&lag), "lag {}", lag);
+    assert!(slot_record.rpc_lag_ms.is_some());
+    assert!(record(&report, 101).unwrap().block_time_lag_ms.is_none());
+
+    let block_time = report.latency.block_time.summary().unwrap();
+    assert_eq!(block_time.count, 1);
+    assert_eq!(block_time.p99_ms, lag);
+    assert_eq!(report.latency.rpc.summary().unwrap().count, 2);
+}
+
+#[tokio::test]
+async fn rpc_lag_leaves_out_the_finalization_delay() {
+    let geyser = MockGeyser::spawn(vec![vec![block(100, 1)]]).await;
+    let ledger = Ledger::with_blocks([100], 1);
+    let config = VerifierConfig {
+        commitment: CommitmentLevel::Confirmed,
+        finalization_delay: Duration::from_secs(2),
+        ..verifier_config()
+    };
+
+    let report = backfill(&geyser, ledger, config, (100, 100)).await.unwrap();
+    let lag = record(&report, 100).unwrap().rpc_lag_ms.unwrap();
+    assert!(lag < 2_000, "lag {}", lag);
+}
+
+#[tokio::test]
+async fn finalization_delay_holds_no_queue_slot() {
+    let geyser = MockGeyser::spawn(vec![vec![
+        block(100, 1),
+        block(101, 1),
+        block(102, 1),
+        block(103, 1),
+    ]])
+    .await;
+    let ledger = Ledger::with_blocks(100..=103, 1);
+    let config = VerifierConfig {
+        rpc_workers: 1,
+        queue_size: 1,
+        commitment: CommitmentLevel::Confirmed,
+        finalization_delay: Duration::from_secs(1),
+        ..verifier_config()
+    };
+
+    let report = backfill(&geyser, ledger, config, (100, 103)).await.unwrap();
+    assert_eq!(report.verified_blocks, 4);
+    // a worker waiting out the delay would hold the stream back for about a second
+    let backpressure = &report.backpressure;
+    assert!(backpressure.enqueue_wait_max < Duration::from_millis(500));
+    assert!(backpressure.queue_wait_max < Duration::from_millis(500));
+}
+
+#[tokio::test]
+async fn verifies_against_a_reference_geyser_stream() {
+    let Event::Block(mut after_skip) = block(103, 1) else {
+        unreachable!()
+    };
+    // 102 was skipped on the reference chain
+    after_skip.parent_slot = 101;
+    let reference = MockGeyser::spawn(vec![vec![
+        block(100, 1),
+        block(101, 2),
+        Event::Block(after_skip),
+        block(104, 1),
+    ]])
+    .await;
+    let geyser = MockGeyser::spawn(vec![vec![
+        block(100, 1),
+        block(101, 1),
+        block(102, 1),
+        block(105, 1),
+    ]])
+    .await;
+    let request = SubscribeRequest {
+        from_slot: Some(100),
+        ..Default::default()
+    };
+
+    let report = backfill(
+        &geyser,
+        GeyserReference::spawn(reference.source(), request),
+        verifier_config(),
+        (100, 104),
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.verified_blocks, 2);
+    assert_eq!(status(&report, 100), Some(BlockStatus::Match));
+    assert_eq!(status(&report, 101), Some(BlockStatus::Mismatch));
+    assert_eq!(
+        record(&report, 102).unwrap().unverified_reason,
+        Some(UnverifiedReason::SlotSkipped)
+    );
+    assert_eq!(report.missing_blocks, 2);
+    assert_eq!(status(&report, 103), Some(BlockStatus::Missing));
+    assert_eq!(status(&report, 104), Some(BlockStatus::Missing));
+}
+
+#[tokio::test]
+async fn tracks_blocks_rolled_back_below_finalized_commitment() {
+    let Event::Block(mut abandoned) = block(101, 2) else {
+        unreachable!()
+    };
+    abandoned.blockhash = "abandoned-fork".to_string();
+    let geyser = MockGeyser::spawn(vec![vec![
+        block(100, 1),
+        Event::Block(abandoned),
+        // the stream switched forks and delivers the finalized 101
+        block(101, 1),
+        // skipped on the finalized chain
+        block(102, 1),
+        block(103, 1),
+    ]])
+    .await;
+    let ledger = Ledger::with_blocks([100, 101, 103], 1);
+    let config = VerifierConfig {
+        commitment: CommitmentLevel::Processed,
+        ..verifier_config()
+    };
+
+    let report = backfill(&geyser, ledger, config, (100, 103)).await.unwrap();
+    assert_eq!(report.duplicate_slots, 1);
+    assert_eq!(report.duplicate_blocks, 0);
+    assert_eq!(report.total_blocks, 5);
+    assert_eq!(report.verified_blocks, 3);
+    assert_eq!(report.mismatched_blocks, 0);
+    assert_eq!(report.missing_blocks, 0);
+    assert_eq!(report.rolled_back_blocks, 2);
+
+    let mut statuses: Vec<_> = report
+        .slots
+        .iter()
+        .filter(|record| record.slot == 101)
+        .map(|record| record.status)
+        .collect();
+    statuses.sort_by_key(|status| *status == BlockStatus::Match);
+    assert_eq!(statuses, vec![BlockStatus::RolledBack, BlockStatus::Match]);
+    assert_eq!(status(&report, 102), Some(BlockStatus::RolledBack));
+    assert_eq!(
+        record(&report, 102).unwrap().error.as_deref(),
+        Some("slot skipped on the finalized chain")
+    );
+}
+
+#[tokio::test]
+async fn verifies_slot_statuses_next_to_blocks() {
+    let geyser = MockGeyser::spawn(vec![vec![
+        slot(100, SlotStatus::SlotProcessed),
+        slot(100, SlotStatus::SlotConfirmed),
+        slot(100, SlotStatus::SlotFinalized),
+        block(100, 1),
+        slot(101, SlotStatus::SlotProcessed),
+        slot(101, SlotStatus::SlotFinalized),
+        // finalized, but its block never comes
+        slot(102, SlotStatus::SlotProcessed),
+        slot(102, SlotStatus::SlotConfirmed),
+        slot(102, SlotStatus::SlotFinalized),
+        block(101, 1),
+        block(103, 1),
+    ]])
+    .await;
+    let ledger = Ledger::with_blocks(100..=103, 1);
+
+    let report = backfill(&geyser, ledger, verifier_config(), (100, 103))
+        .await
+        .unwrap();
+    let slot_status = &report.slot_status;
+    assert_eq!(slot_status.updates, 8);
+    assert_eq!(slot_status.finalized_slots, 3);
+    assert_eq!(slot_status.count(SlotAnomalyKind::SkippedStatus), 1);
+    assert_eq!(slot_status.anomalies[0].slot, 101);
+    assert_eq!(slot_status.count(SlotAnomalyKind::Undelivered), 1);
+    assert_eq!(slot_status.anomalies[1].slot, 102);
+    assert_eq!(report.missing_blocks, 1);
+}
+
+// The gRPC side of the transaction captured in tests/fixtures/deep_compare.
+fn fixture_meta() -> TransactionStatusMeta {
+    let token_balance = |amount: &str, ui_amount: f64, ui_amount_string: &str| TokenBalance {
+        account_index: 0,
+        mint: "So11111111111111111111111111111111111111112".to_string(),
+        ui_token_amount: Some(UiTokenAmount {
+            ui_amount,
+            decimals: 9,
+            amount: amount.to_string(),
+            ui_amount_string: ui_amount_string.to_string(),
+        }),
+        owner: "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM".to_string(),
+        program_id: "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(),
+    };
+    TransactionStatusMeta {
+        fee: 5000,
+        pre_balances: vec![1_000_000, 1],
+        post_balances: vec![995_000, 1],
+        inner_instructions: vec![InnerInstructions {
+            index: 0,
+            instructions: vec![InnerInstruction {
+                program_id_index: 1,
+                accounts: vec![0],
+                data: vec![1, 2, 3],
+                stack_height: Some(2),
+            }],
+        }],
+        log_messages: vec![
+            "Program 11111111111111111111111111111111 invoke [1]".to_string(),
+            "Program 11111111111111111111111111111111 success".to_string(),
+        ],
+        pre_token_balances: vec![token_balance("1000000000", 1.0, "1")],
+        post_token_balances: vec![token_balance("500000000", 0.5, "0.5")],
+        loaded_readonly_addresses: vec![
+            bs58::decode("SysvarC1ock11111111111111111111111111111111")
+                .into_vec()
+                .unwrap(),
+        ],
+        return_data: Some(ReturnData {
+            program_id: vec![0; 32],
+            data: vec![1, 2, 3],
+        }),
+        compute_units_consumed: Some(150),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn deep_compares_transaction_metas() {
+    let with_meta = |slot, edit: fn(&mut TransactionStatusMeta)| {
+        let Event::Block(mut block) = block(slot, 1) else {
+            unreachable!()
+        };
+        let mut meta = fixture_meta();
+        edit(&mut meta);
+        block.transactions[0].meta = Some(meta);
+        Event::Block(block)
+    };
+    let geyser = MockGeyser::spawn(vec![vec![
+        with_meta(100, |_| {}),
+        with_meta(101, |meta| meta.fee = 6000),
+        // not a bincode-encoded TransactionError
+        with_meta(102, |meta| {
+            meta.err = Some(TransactionError {
+                err: vec![0xff, 0xff, 0xff],
+            })
+        }),
+    ]])
+    .await;
+    let fixtures =
+        Fixtures::open(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/deep_compare"))
+            .unwrap();
+    let config = VerifierConfig {
+        deep_compare: true,
+        ..verifier_config()
+    };
+
+    let report = backfill(&geyser, fixtures, config, (100, 102))
+        .await
+        .unwrap();
+    assert_eq!(status(&report, 100), Some(BlockStatus::Match));
+    assert!(record(&report, 100).unwrap().meta_diffs.is_empty());
+    assert_eq!(status(&report, 101), Some(BlockStatus::Mismatch));
+    assert_eq!(
+        record(&report, 101).unwrap().meta_diffs[0].fields,
+        vec!["fee"]
+    );
+    assert_eq!(status(&report, 102), Some(BlockStatus::Unverified));
+    assert_eq!(
+        record(&report, 102).unwrap().unverified_reason,
+        Some(UnverifiedReason::UndecodableMeta)
+    );
+    assert_eq!(report.verified_blocks, 2);
+}
+
+// Streams a backfill into a recording at `path`, replays it and checks both
+// runs verified the same blocks.
+async fn record_and_replay(path: &Path) {
+    init_logger();
+    let geyser = MockGeyser::spawn(vec![vec![
+        block(100, 1),
+        block(101, 2),
+        // long enough for a three byte length prefix
+        block(102, 300),
+    ]])
+    .await;
+    let mut ledger = Ledger::with_blocks(100..=101, 1);
+    ledger.blocks.extend(Ledger::with_blocks([102], 300).blocks);
+
+    let request = SubscribeRequest {
+        from_slot: Some(100),
+        ..Default::default()
+    };
+    let verifier = Verifier::new(verifier_config(), ledger.clone(), Some((100, 102)));
+    let recorder = Recorder::create(path).unwrap();
+    tokio::time::timeout(
+        Duration::from_secs(30),
+        verifier.stream(&geyser.source(), request, Some(recorder)),
+    )
+    .await
+    .expect("backfill did not complete")
+    .unwrap();
+    let streamed = verifier.finish(&mut Shutdown::listen()).await;
+
+    let compressed = fs::read(path)
+        .unwrap()
+        .starts_with(&[0x28, 0xb5, 0x2f, 0xfd]);
+    assert_eq!(compressed, path.extension().is_some_and(|ext| ext == "zst"));
+
+    let verifier = Verifier::new(verifier_config(), ledger, None);
+    verifier
+        .replay(Recording::open(path).unwrap())
+        .await
+        .unwrap();
+    let replayed = verifier.finish(&mut Shutdown::listen()).await;
+    fs::remove_file(path).unwrap();
+
+    for report in [&streamed, &replayed] {
+        assert_eq!(report.total_blocks, 3);
+        assert_eq!(report.verified_blocks, 3);
+        assert_eq!(report.mismatched_blocks, 1);
+        assert_eq!(status(report, 101), Some(BlockStatus::Mismatch));
+        assert_eq!(record(report, 102).unwrap().rpc_tx_count, Some(300));
+    }
+}
+
+#[tokio::test]
+async fn replays_a_plain_recording() {
+    let path = std::env::temp_dir().join(format!("recording-{}.bin", std::process::id()));
+    record_and_replay(&path).await;
+}
+
+#[tokio::test]
+async fn replays_a_compressed_recording() {
+    let path = std::env::temp_dir().join(format!("recording-{}.bin.zst", std::process::id()));
+    record_and_replay(&path).await;
+}
@@ -0,0 +1,69 @@
+use {
+    serde_json::Value,
+    solana_grpc_integrity_checker::{
+        Report,
+        report::{BlockStatus, SlotRecord},
+        window::run_windows,
+    },
+    std::{
+        fs,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+};
+
+fn verified(slot: u64, status: BlockStatus, rpc_lag_ms: i64) -> SlotRecord {
+    let mut record = SlotRecord::new(slot, status);
+    record.rpc_lag_ms = Some(rpc_lag_ms);
+    record
+}
+
+#[tokio::test]
+async fn windows_take_their_records_and_samples_along() {
+    let report = Arc::new(Mutex::new(Report::default()));
+    {
+        let mut rep = report.lock().unwrap();
+        rep.record_verified(verified(100, BlockStatus::Match, 100));
+        rep.record_verified(verified(101, BlockStatus::Mismatch, 300));
+    }
+    let path = std::env::temp_dir().join(format!("windows-{}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let windows = tokio::spawn(run_windows(
+        Duration::from_millis(100),
+        report.clone(),
+        Some(path.clone()),
+    ));
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    report
+        .lock()
+        .unwrap()
+        .record_verified(verified(102, BlockStatus::Match, 200));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    windows.abort();
+
+    let lines: Vec<Value> = fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(lines[0]["verified_blocks"], 2);
+    assert_eq!(lines[0]["latency"]["rpc"]["count"], 2);
+    assert_eq!(lines[0]["latency"]["rpc"]["max_ms"], 300);
+    let slots = lines[0]["slots"].as_array().unwrap();
+    assert_eq!(slots.len(), 1);
+    assert_eq!(slots[0]["slot"], 101);
+
+    // the second window only covers what was verified after the first
+    assert_eq!(lines[1]["verified_blocks"], 1);
+    assert_eq!(lines[1]["latency"]["rpc"]["count"], 1);
+    assert_eq!(lines[1]["latency"]["rpc"]["max_ms"], 200);
+    assert!(lines[1].get("slots").is_none());
+
+    let rep = report.lock().unwrap();
+    assert_eq!(rep.verified_blocks, 3);
+    assert!(rep.slots.is_empty());
+    assert!(rep.latency.rpc.summary().is_none());
+}